@@ -0,0 +1,209 @@
+//! Conversions from [`ErrorCode`] to [`std::io::Error`].
+
+use crate::filesystem::types::ErrorCode;
+#[cfg(feature = "std")]
+use std::io;
+
+impl ErrorCode {
+    /// Returns the `errno` value of this error as defined by `wasi-libc`, the
+    /// same value returned by [`std::io::Error::raw_os_error`] for errors
+    /// raised by the standard library on WASI targets.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        Some(match self {
+            ErrorCode::Access => 2,
+            ErrorCode::WouldBlock => 6,
+            ErrorCode::Already => 7,
+            ErrorCode::BadDescriptor => 8,
+            ErrorCode::Busy => 10,
+            ErrorCode::Deadlock => 16,
+            ErrorCode::Quota => 19,
+            ErrorCode::Exist => 20,
+            ErrorCode::FileTooLarge => 22,
+            ErrorCode::IllegalByteSequence => 25,
+            ErrorCode::InProgress => 26,
+            ErrorCode::Interrupted => 27,
+            ErrorCode::Invalid => 28,
+            ErrorCode::Io => 29,
+            ErrorCode::IsDirectory => 31,
+            ErrorCode::Loop => 32,
+            ErrorCode::TooManyLinks => 34,
+            ErrorCode::MessageSize => 35,
+            ErrorCode::NameTooLong => 37,
+            ErrorCode::NoDevice => 43,
+            ErrorCode::NoEntry => 44,
+            ErrorCode::NoLock => 46,
+            ErrorCode::InsufficientMemory => 48,
+            ErrorCode::InsufficientSpace => 51,
+            ErrorCode::NotDirectory => 54,
+            ErrorCode::NotEmpty => 55,
+            ErrorCode::NotRecoverable => 56,
+            ErrorCode::Unsupported => 58,
+            ErrorCode::NoTty => 59,
+            ErrorCode::NoSuchDevice => 60,
+            ErrorCode::Overflow => 61,
+            ErrorCode::NotPermitted => 63,
+            ErrorCode::Pipe => 64,
+            ErrorCode::ReadOnly => 69,
+            ErrorCode::InvalidSeek => 70,
+            ErrorCode::TextFileBusy => 74,
+            ErrorCode::CrossDevice => 75,
+        })
+    }
+
+    /// Returns the [`io::ErrorKind`] corresponding to this error.
+    #[cfg(feature = "std")]
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            ErrorCode::Access | ErrorCode::NotPermitted => io::ErrorKind::PermissionDenied,
+            ErrorCode::WouldBlock => io::ErrorKind::WouldBlock,
+            ErrorCode::Busy => io::ErrorKind::ResourceBusy,
+            ErrorCode::Deadlock => io::ErrorKind::Deadlock,
+            ErrorCode::Quota => io::ErrorKind::QuotaExceeded,
+            ErrorCode::Exist => io::ErrorKind::AlreadyExists,
+            ErrorCode::FileTooLarge => io::ErrorKind::FileTooLarge,
+            ErrorCode::IllegalByteSequence => io::ErrorKind::InvalidData,
+            ErrorCode::Interrupted => io::ErrorKind::Interrupted,
+            ErrorCode::Invalid => io::ErrorKind::InvalidInput,
+            ErrorCode::IsDirectory => io::ErrorKind::IsADirectory,
+            ErrorCode::TooManyLinks => io::ErrorKind::TooManyLinks,
+            ErrorCode::NameTooLong => io::ErrorKind::InvalidFilename,
+            ErrorCode::NoEntry => io::ErrorKind::NotFound,
+            ErrorCode::InsufficientMemory => io::ErrorKind::OutOfMemory,
+            ErrorCode::InsufficientSpace => io::ErrorKind::StorageFull,
+            ErrorCode::NotDirectory => io::ErrorKind::NotADirectory,
+            ErrorCode::NotEmpty => io::ErrorKind::DirectoryNotEmpty,
+            ErrorCode::Unsupported => io::ErrorKind::Unsupported,
+            ErrorCode::Pipe => io::ErrorKind::BrokenPipe,
+            ErrorCode::ReadOnly => io::ErrorKind::ReadOnlyFilesystem,
+            ErrorCode::InvalidSeek => io::ErrorKind::NotSeekable,
+            ErrorCode::TextFileBusy => io::ErrorKind::ExecutableFileBusy,
+            ErrorCode::CrossDevice => io::ErrorKind::CrossesDevices,
+            ErrorCode::Already
+            | ErrorCode::BadDescriptor
+            | ErrorCode::InProgress
+            | ErrorCode::Io
+            | ErrorCode::Loop
+            | ErrorCode::MessageSize
+            | ErrorCode::NoDevice
+            | ErrorCode::NoLock
+            | ErrorCode::NotRecoverable
+            | ErrorCode::NoTty
+            | ErrorCode::NoSuchDevice
+            | ErrorCode::Overflow => io::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ErrorCode> for io::Error {
+    fn from(code: ErrorCode) -> io::Error {
+        io::Error::new(code.kind(), code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every error code along with its `errno` name and value in `wasi-libc`.
+    const ERRNO: &[(ErrorCode, &str, i32)] = &[
+        (ErrorCode::Access, "EACCES", 2),
+        (ErrorCode::WouldBlock, "EAGAIN", 6),
+        (ErrorCode::Already, "EALREADY", 7),
+        (ErrorCode::BadDescriptor, "EBADF", 8),
+        (ErrorCode::Busy, "EBUSY", 10),
+        (ErrorCode::Deadlock, "EDEADLK", 16),
+        (ErrorCode::Quota, "EDQUOT", 19),
+        (ErrorCode::Exist, "EEXIST", 20),
+        (ErrorCode::FileTooLarge, "EFBIG", 22),
+        (ErrorCode::IllegalByteSequence, "EILSEQ", 25),
+        (ErrorCode::InProgress, "EINPROGRESS", 26),
+        (ErrorCode::Interrupted, "EINTR", 27),
+        (ErrorCode::Invalid, "EINVAL", 28),
+        (ErrorCode::Io, "EIO", 29),
+        (ErrorCode::IsDirectory, "EISDIR", 31),
+        (ErrorCode::Loop, "ELOOP", 32),
+        (ErrorCode::TooManyLinks, "EMLINK", 34),
+        (ErrorCode::MessageSize, "EMSGSIZE", 35),
+        (ErrorCode::NameTooLong, "ENAMETOOLONG", 37),
+        (ErrorCode::NoDevice, "ENODEV", 43),
+        (ErrorCode::NoEntry, "ENOENT", 44),
+        (ErrorCode::NoLock, "ENOLCK", 46),
+        (ErrorCode::InsufficientMemory, "ENOMEM", 48),
+        (ErrorCode::InsufficientSpace, "ENOSPC", 51),
+        (ErrorCode::NotDirectory, "ENOTDIR", 54),
+        (ErrorCode::NotEmpty, "ENOTEMPTY", 55),
+        (ErrorCode::NotRecoverable, "ENOTRECOVERABLE", 56),
+        (ErrorCode::Unsupported, "ENOTSUP", 58),
+        (ErrorCode::NoTty, "ENOTTY", 59),
+        (ErrorCode::NoSuchDevice, "ENXIO", 60),
+        (ErrorCode::Overflow, "EOVERFLOW", 61),
+        (ErrorCode::NotPermitted, "EPERM", 63),
+        (ErrorCode::Pipe, "EPIPE", 64),
+        (ErrorCode::ReadOnly, "EROFS", 69),
+        (ErrorCode::InvalidSeek, "ESPIPE", 70),
+        (ErrorCode::TextFileBusy, "ETXTBSY", 74),
+        (ErrorCode::CrossDevice, "EXDEV", 75),
+    ];
+
+    #[test]
+    fn raw_os_errors() {
+        for (code, name, errno) in ERRNO {
+            assert_eq!(code.raw_os_error(), Some(*errno), "{name}");
+        }
+    }
+
+    #[test]
+    fn raw_os_errors_are_distinct() {
+        for (i, (_, a, x)) in ERRNO.iter().enumerate() {
+            for (_, b, y) in &ERRNO[i + 1..] {
+                assert_ne!(x, y, "{a} and {b}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn kinds() {
+        use io::ErrorKind::*;
+        let kinds = [
+            (ErrorCode::Access, PermissionDenied),
+            (ErrorCode::NotPermitted, PermissionDenied),
+            (ErrorCode::WouldBlock, WouldBlock),
+            (ErrorCode::Exist, AlreadyExists),
+            (ErrorCode::NoEntry, NotFound),
+            (ErrorCode::Invalid, InvalidInput),
+            (ErrorCode::IllegalByteSequence, InvalidData),
+            (ErrorCode::Interrupted, Interrupted),
+            (ErrorCode::IsDirectory, IsADirectory),
+            (ErrorCode::NotDirectory, NotADirectory),
+            (ErrorCode::NotEmpty, DirectoryNotEmpty),
+            (ErrorCode::ReadOnly, ReadOnlyFilesystem),
+            (ErrorCode::InsufficientSpace, StorageFull),
+            (ErrorCode::InsufficientMemory, OutOfMemory),
+            (ErrorCode::FileTooLarge, FileTooLarge),
+            (ErrorCode::NameTooLong, InvalidFilename),
+            (ErrorCode::InvalidSeek, NotSeekable),
+            (ErrorCode::CrossDevice, CrossesDevices),
+            (ErrorCode::Pipe, BrokenPipe),
+            (ErrorCode::Unsupported, Unsupported),
+            (ErrorCode::Io, Other),
+            (ErrorCode::Loop, Other),
+            (ErrorCode::BadDescriptor, Other),
+        ];
+        for (code, kind) in kinds {
+            assert_eq!(code.kind(), kind, "{code:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn into_io_error() {
+        for (code, name, _) in ERRNO {
+            let err = io::Error::from(*code);
+            assert_eq!(err.kind(), code.kind(), "{name}");
+            let inner = err.get_ref().and_then(|e| e.downcast_ref::<ErrorCode>());
+            assert_eq!(inner, Some(code), "{name}");
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use super::preopens::with_resolved;
+use super::stream_error;
+use crate::filesystem::types::{
+    Descriptor, DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry, ErrorCode,
+    NewTimestamp, OpenFlags, PathFlags,
+};
+use crate::io::streams::StreamError;
+use std::format;
+use std::io;
+use std::path::Path;
+use std::vec::Vec;
+
+/// The most bytes spliced from one file to another at once.
+const SPLICE_CHUNK: u64 = 64 * 1024;
+
+/// Copies the contents of the file at `from` to `to`, creating `to` if it
+/// doesn't exist and replacing its contents if it does, and returns the number
+/// of bytes copied.
+///
+/// Unlike [`std::fs::copy`], the access and modification times of `from` are
+/// preserved as well. The contents are spliced between the streams of both
+/// files, which hosts may implement without copying them through the
+/// component. If copying fails once `to` has been opened then it's removed
+/// again.
+pub fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
+    with_resolved(from.as_ref(), |from_dir, from| {
+        with_resolved(to.as_ref(), |to_dir, to| {
+            copy_file_at(from_dir, from, to_dir, to)
+        })
+    })
+}
+
+/// Recursively copies the directory at `from` to a new directory at `to`,
+/// which must not exist yet.
+///
+/// Symbolic links are recreated rather than followed, and the access and
+/// modification times of files and directories are preserved. Fails with
+/// [`io::ErrorKind::Unsupported`] on anything that's neither a file, a
+/// directory nor a symbolic link, and with [`io::ErrorKind::InvalidInput`] if
+/// `to` is inside of `from`. If copying fails then `to` is removed again.
+pub fn copy_dir_all<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    with_resolved(from.as_ref(), |from_dir, from| {
+        with_resolved(to.as_ref(), |to_dir, to| {
+            let stat = from_dir.stat_at(PathFlags::SYMLINK_FOLLOW, from)?;
+            if stat.type_ != DescriptorType::Directory {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{from}` is not a directory"),
+                ));
+            }
+            // Copies made inside of `from` through symbolic links or other
+            // preopens are only caught once they show up while copying.
+            if from_dir.is_same_object(to_dir) && is_within(from, to) {
+                return Err(copy_into_itself());
+            }
+            copy_dir_at(from_dir, from, PathFlags::SYMLINK_FOLLOW, to_dir, to)?;
+            set_times_from(to_dir, to, &stat)
+        })
+    })
+}
+
+/// Moves the file, directory or symbolic link at `from` to `to`, replacing
+/// `to` if it's a file.
+///
+/// This is a rename when possible. If `from` and `to` are on different
+/// devices, the host fails the rename with [`ErrorCode::CrossDevice`]. `from`
+/// is then copied like [`copy_file`] or [`copy_dir_all`] and removed
+/// afterwards, so the move is no longer atomic. Files and symbolic links are
+/// removed from `to` again if they can't be removed from `from`.
+pub fn move_path<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    with_resolved(from.as_ref(), |from_dir, from| {
+        with_resolved(to.as_ref(), |to_dir, to| {
+            match from_dir.rename_at(from, to_dir, to) {
+                Err(ErrorCode::CrossDevice) => {}
+                result => return Ok(result?),
+            }
+            let stat = from_dir.stat_at(PathFlags::empty(), from)?;
+            match stat.type_ {
+                DescriptorType::Directory => {
+                    copy_dir_at(from_dir, from, PathFlags::empty(), to_dir, to)?;
+                    set_times_from(to_dir, to, &stat)?;
+                    remove_dir_all_at(from_dir, from)
+                }
+                DescriptorType::SymbolicLink => {
+                    to_dir.symlink_at(&from_dir.readlink_at(from)?, to)?;
+                    remove_moved(from_dir, from, to_dir, to)
+                }
+                _ => {
+                    copy_file_at(from_dir, from, to_dir, to)?;
+                    remove_moved(from_dir, from, to_dir, to)
+                }
+            }
+        })
+    })
+}
+
+/// Removes `from` once it has been copied to `to`, removing `to` again if
+/// that fails so that the move either happens or doesn't.
+fn remove_moved(
+    from_dir: &Descriptor,
+    from: &str,
+    to_dir: &Descriptor,
+    to: &str,
+) -> io::Result<()> {
+    let result = from_dir.unlink_file_at(from);
+    if result.is_err() {
+        let _ = to_dir.unlink_file_at(to);
+    }
+    Ok(result?)
+}
+
+/// Removes the directory at `path` along with everything in it.
+///
+/// Symbolic links are removed rather than followed, including `path` itself.
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    with_resolved(path.as_ref(), |dir, path| {
+        if dir.stat_at(PathFlags::empty(), path)?.type_ == DescriptorType::SymbolicLink {
+            return Ok(dir.unlink_file_at(path)?);
+        }
+        remove_dir_all_at(dir, path)
+    })
+}
+
+/// Removes the directory `name` in `parent` along with everything in it,
+/// without following symbolic links.
+pub(super) fn remove_dir_all_at(parent: &Descriptor, name: &str) -> io::Result<()> {
+    let dir = parent.open_at(
+        PathFlags::empty(),
+        name,
+        OpenFlags::DIRECTORY,
+        DescriptorFlags::READ | DescriptorFlags::MUTATE_DIRECTORY,
+    )?;
+    for entry in entries(&dir)? {
+        if entry_type(&dir, &entry)? == DescriptorType::Directory {
+            remove_dir_all_at(&dir, &entry.name)?;
+        } else {
+            dir.unlink_file_at(&entry.name)?;
+        }
+    }
+    drop(dir);
+    Ok(parent.remove_directory_at(name)?)
+}
+
+/// Copies the file `from` in `from_dir` to `to` in `to_dir`, removing `to`
+/// again if copying fails once it has been opened.
+fn copy_file_at(
+    from_dir: &Descriptor,
+    from: &str,
+    to_dir: &Descriptor,
+    to: &str,
+) -> io::Result<u64> {
+    let from = from_dir.open_at(
+        PathFlags::SYMLINK_FOLLOW,
+        from,
+        OpenFlags::empty(),
+        DescriptorFlags::READ,
+    )?;
+    let to_file = to_dir.open_at(
+        PathFlags::SYMLINK_FOLLOW,
+        to,
+        OpenFlags::CREATE | OpenFlags::TRUNCATE,
+        DescriptorFlags::WRITE,
+    )?;
+    let result = copy_file_contents(&from, &to_file);
+    if result.is_err() {
+        drop(to_file);
+        let _ = to_dir.unlink_file_at(to);
+    }
+    result
+}
+
+fn copy_file_contents(from: &Descriptor, to: &Descriptor) -> io::Result<u64> {
+    let stat = from.stat()?;
+
+    // NB: the streams are children of the descriptors so they're declared
+    // after them to be dropped first.
+    let input = from.read_via_stream(0)?;
+    let output = to.write_via_stream(0)?;
+    let mut copied = 0;
+    loop {
+        match output.blocking_splice(&input, SPLICE_CHUNK) {
+            Ok(n) => copied += n,
+            Err(StreamError::Closed) => {
+                // `splice` doesn't say which of the two streams was closed, so
+                // check the output: if it's still accepting writes then the
+                // input has simply reached its end.
+                output.check_write().map_err(stream_error)?;
+                break;
+            }
+            Err(e) => return Err(stream_error(e)),
+        }
+    }
+    output.blocking_flush().map_err(stream_error)?;
+    drop(output);
+    drop(input);
+
+    let (accessed, modified) = timestamps(&stat);
+    to.set_times(accessed, modified)?;
+    Ok(copied)
+}
+
+/// Copies the directory `from` in `from_dir` to a new directory `to` in
+/// `to_dir`, removing `to` again if copying its contents fails.
+fn copy_dir_at(
+    from_dir: &Descriptor,
+    from: &str,
+    path_flags: PathFlags,
+    to_dir: &Descriptor,
+    to: &str,
+) -> io::Result<()> {
+    let from = from_dir.open_at(
+        path_flags,
+        from,
+        OpenFlags::DIRECTORY,
+        DescriptorFlags::READ,
+    )?;
+    to_dir.create_directory_at(to)?;
+    let result = open_mutable_dir(to_dir, to).and_then(|root| copy_contents(&from, &root, &root));
+    if result.is_err() {
+        let _ = remove_dir_all_at(to_dir, to);
+    }
+    result
+}
+
+/// Copies the contents of the directory `from` into the directory `to`, both
+/// of which are open, where `root` is the directory the copy started in.
+fn copy_contents(from: &Descriptor, to: &Descriptor, root: &Descriptor) -> io::Result<()> {
+    for entry in entries(from)? {
+        let name = entry.name.as_str();
+        match entry_type(from, &entry)? {
+            DescriptorType::Directory => {
+                let from_child = from.open_at(
+                    PathFlags::empty(),
+                    name,
+                    OpenFlags::DIRECTORY,
+                    DescriptorFlags::READ,
+                )?;
+                // The copy itself shows up in `from` if it's being made inside
+                // of it, and copying it again would never end.
+                if from_child.is_same_object(root) {
+                    return Err(copy_into_itself());
+                }
+                to.create_directory_at(name)?;
+                copy_contents(&from_child, &open_mutable_dir(to, name)?, root)?;
+                set_times_from(to, name, &from_child.stat()?)?;
+            }
+            DescriptorType::SymbolicLink => {
+                to.symlink_at(&from.readlink_at(name)?, name)?;
+            }
+            DescriptorType::RegularFile => {
+                copy_file_at(from, name, to, name)?;
+            }
+            type_ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("cannot copy `{name}` of type {type_:?}"),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy_into_itself() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot copy a directory into itself",
+    )
+}
+
+/// Returns whether the path `to` is inside of the path `from`, relative to
+/// the same directory.
+///
+/// Paths with `..` components are left for the host to resolve, so they're
+/// never considered to be inside of each other.
+fn is_within(from: &str, to: &str) -> bool {
+    fn components(path: &str) -> Vec<&str> {
+        path.split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect()
+    }
+    let (from, to) = (components(from), components(to));
+    !from.contains(&"..") && !to.contains(&"..") && to.len() > from.len() && to.starts_with(&from)
+}
+
+fn open_mutable_dir(dir: &Descriptor, name: &str) -> io::Result<Descriptor> {
+    Ok(dir.open_at(
+        PathFlags::empty(),
+        name,
+        OpenFlags::DIRECTORY,
+        DescriptorFlags::READ | DescriptorFlags::MUTATE_DIRECTORY,
+    )?)
+}
+
+/// Returns the type of `entry` of `dir`, asking the host for it if the
+/// directory listing didn't say.
+fn entry_type(dir: &Descriptor, entry: &DirectoryEntry) -> io::Result<DescriptorType> {
+    match entry.type_ {
+        DescriptorType::Unknown => Ok(dir.stat_at(PathFlags::empty(), &entry.name)?.type_),
+        type_ => Ok(type_),
+    }
+}
+
+/// Returns every entry of `dir` other than `.` and `..`.
+///
+/// Entries are collected up front since changing a directory while it's being
+/// read may cause entries to be skipped.
+fn entries(dir: &Descriptor) -> io::Result<Vec<DirectoryEntry>> {
+    let mut entries = Vec::new();
+    let stream = dir.read_directory()?;
+    while let Some(entry) = stream.read_directory_entry()? {
+        if entry.name != "." && entry.name != ".." {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+fn set_times_from(dir: &Descriptor, path: &str, stat: &DescriptorStat) -> io::Result<()> {
+    let (accessed, modified) = timestamps(stat);
+    Ok(dir.set_times_at(PathFlags::empty(), path, accessed, modified)?)
+}
+
+fn timestamps(stat: &DescriptorStat) -> (NewTimestamp, NewTimestamp) {
+    let timestamp = |datetime| match datetime {
+        Some(datetime) => NewTimestamp::Timestamp(datetime),
+        None => NewTimestamp::NoChange,
+    };
+    (
+        timestamp(stat.data_access_timestamp),
+        timestamp(stat.data_modification_timestamp),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::types::Datetime;
+
+    #[test]
+    fn paths_within() {
+        let cases = [
+            ("a", "a/b", true),
+            ("a", "a/b/c", true),
+            ("a/b", "a/b/c", true),
+            ("./a/", "a//b", true),
+            (".", "a", true),
+            ("a", "a", false),
+            ("a", "./a/.", false),
+            ("a", "ab", false),
+            ("a/b", "a", false),
+            ("a", "b/a", false),
+            // These are left to the host.
+            ("a", "a/../a/b", false),
+            ("a/..", "a/b", false),
+        ];
+        for (from, to, within) in cases {
+            assert_eq!(is_within(from, to), within, "{to} in {from}");
+        }
+    }
+
+    fn stat(accessed: Option<Datetime>, modified: Option<Datetime>) -> DescriptorStat {
+        DescriptorStat {
+            type_: DescriptorType::RegularFile,
+            link_count: 1,
+            size: 0,
+            data_access_timestamp: accessed,
+            data_modification_timestamp: modified,
+            status_change_timestamp: Some(Datetime {
+                seconds: 3,
+                nanoseconds: 0,
+            }),
+        }
+    }
+
+    #[test]
+    fn copied_timestamps() {
+        let accessed = Datetime {
+            seconds: 1,
+            nanoseconds: 2,
+        };
+        let modified = Datetime {
+            seconds: 1_700_000_000,
+            nanoseconds: 999_999_999,
+        };
+        let is = |timestamp, expected: Option<Datetime>| match (timestamp, expected) {
+            (NewTimestamp::Timestamp(t), Some(e)) => {
+                t.seconds == e.seconds && t.nanoseconds == e.nanoseconds
+            }
+            (NewTimestamp::NoChange, None) => true,
+            _ => false,
+        };
+
+        let (a, m) = timestamps(&stat(Some(accessed), Some(modified)));
+        assert!(is(a, Some(accessed)) && is(m, Some(modified)));
+        let (a, m) = timestamps(&stat(None, Some(modified)));
+        assert!(is(a, None) && is(m, Some(modified)));
+        let (a, m) = timestamps(&stat(Some(accessed), None));
+        assert!(is(a, Some(accessed)) && is(m, None));
+        // The status change time can't be set and is never copied.
+        let (a, m) = timestamps(&stat(None, None));
+        assert!(is(a, None) && is(m, None));
+    }
+}
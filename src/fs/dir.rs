@@ -0,0 +1,295 @@
+use super::preopens::with_resolved;
+use super::{File, Metadata, OpenOptions, ReadDir};
+use crate::filesystem::types::{Descriptor, DescriptorFlags, ErrorCode, OpenFlags, PathFlags};
+use std::format;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// A directory which paths are resolved relative to, similar to `Dir` from
+/// the `cap-std` crate.
+///
+/// A [`Descriptor`] is already a capability to the directory it refers to, so
+/// a `Dir` can only reach what's inside of it. Every method checks its paths
+/// before passing them to the host and fails with
+/// [`io::ErrorKind::PermissionDenied`] on absolute paths and on paths whose
+/// `..` components lead outside of the directory, so that they fail the same
+/// way on every host.
+///
+/// ```no_run
+/// use std::io::Write;
+/// use wasi::fs::Dir;
+///
+/// let data = Dir::open_ambient("/data")?;
+/// data.create_dir("logs")?;
+/// let logs = data.open_dir("logs")?;
+/// logs.create("today.txt")?.write_all(b"hello")?;
+/// assert!(logs.open("../../etc/passwd").is_err());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Dir {
+    descriptor: Descriptor,
+}
+
+impl Dir {
+    /// Opens the directory at `path`, resolved against the preopened
+    /// directories like every other function in this module.
+    ///
+    /// This is the only way to get a `Dir` from a path rather than from
+    /// another `Dir`, so it isn't restricted like the other methods.
+    pub fn open_ambient<P: AsRef<Path>>(path: P) -> io::Result<Dir> {
+        with_resolved(path.as_ref(), |dir, path| {
+            Ok(Dir {
+                descriptor: open_dir_at(dir, path)?,
+            })
+        })
+    }
+
+    /// Wraps the open directory `descriptor`.
+    pub fn from_descriptor(descriptor: Descriptor) -> Dir {
+        Dir { descriptor }
+    }
+
+    /// Opens the file at `path` for reading.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        self.open_with(path, OpenOptions::new().read(true))
+    }
+
+    /// Opens the file at `path` with `options`.
+    pub fn open_with<P: AsRef<Path>>(&self, path: P, options: &OpenOptions) -> io::Result<File> {
+        options.open_at(&self.descriptor, check(path.as_ref())?)
+    }
+
+    /// Opens the file at `path` for writing, creating it if it doesn't exist
+    /// and truncating it if it does.
+    pub fn create<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        self.open_with(
+            path,
+            OpenOptions::new().write(true).create(true).truncate(true),
+        )
+    }
+
+    /// Opens the directory at `path`.
+    pub fn open_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<Dir> {
+        Ok(Dir {
+            descriptor: open_dir_at(&self.descriptor, check(path.as_ref())?)?,
+        })
+    }
+
+    /// Creates a new, empty directory at `path`.
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Ok(self.descriptor.create_directory_at(check(path.as_ref())?)?)
+    }
+
+    /// Returns an iterator over the entries of the directory at `path`, whose
+    /// paths are joined onto `path`.
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<ReadDir> {
+        let path = path.as_ref();
+        let dir = open_dir_at(&self.descriptor, check(path)?)?;
+        Ok(ReadDir::new(dir, path)?)
+    }
+
+    /// Removes the file at `path`.
+    pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Ok(self.descriptor.unlink_file_at(check(path.as_ref())?)?)
+    }
+
+    /// Removes the empty directory at `path`.
+    pub fn remove_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Ok(self.descriptor.remove_directory_at(check(path.as_ref())?)?)
+    }
+
+    /// Renames the file or directory at `from` to `to` in `to_dir`, replacing
+    /// `to` if it already exists.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        from: P,
+        to_dir: &Dir,
+        to: Q,
+    ) -> io::Result<()> {
+        let from = check(from.as_ref())?;
+        let to = check(to.as_ref())?;
+        Ok(self.descriptor.rename_at(from, &to_dir.descriptor, to)?)
+    }
+
+    /// Creates a hard link at `dst` in `dst_dir` to the file at `src`.
+    pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        src: P,
+        dst_dir: &Dir,
+        dst: Q,
+    ) -> io::Result<()> {
+        let src = check(src.as_ref())?;
+        let dst = check(dst.as_ref())?;
+        Ok(self
+            .descriptor
+            .link_at(PathFlags::empty(), src, &dst_dir.descriptor, dst)?)
+    }
+
+    /// Creates a symbolic link at `link` pointing to `original`.
+    ///
+    /// `original` is stored as is and resolved relative to the directory of
+    /// `link` when the link is followed. Like every other path, it fails with
+    /// [`io::ErrorKind::PermissionDenied`] if it's absolute or leads outside
+    /// of this directory.
+    pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(&self, original: P, link: Q) -> io::Result<()> {
+        let link = link.as_ref();
+        let original = check_target(original.as_ref(), link)?;
+        Ok(self.descriptor.symlink_at(original, check(link)?)?)
+    }
+
+    /// Returns the target of the symbolic link at `path`.
+    pub fn read_link<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        Ok(self.descriptor.readlink_at(check(path.as_ref())?)?.into())
+    }
+
+    /// Returns the metadata of the file or directory at `path`, following
+    /// symbolic links.
+    pub fn metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<Metadata> {
+        let path = check(path.as_ref())?;
+        Ok(self
+            .descriptor
+            .stat_at(PathFlags::SYMLINK_FOLLOW, path)?
+            .into())
+    }
+
+    /// Returns the metadata of the file or directory at `path` without
+    /// following symbolic links.
+    pub fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> io::Result<Metadata> {
+        let path = check(path.as_ref())?;
+        Ok(self.descriptor.stat_at(PathFlags::empty(), path)?.into())
+    }
+
+    /// Returns the metadata of this directory.
+    pub fn dir_metadata(&self) -> io::Result<Metadata> {
+        Ok(Metadata::from_descriptor(&self.descriptor)?)
+    }
+
+    /// Returns the underlying descriptor.
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    /// Consumes this directory, returning the underlying descriptor.
+    pub fn into_descriptor(self) -> Descriptor {
+        self.descriptor
+    }
+}
+
+impl From<Descriptor> for Dir {
+    fn from(descriptor: Descriptor) -> Dir {
+        Dir::from_descriptor(descriptor)
+    }
+}
+
+/// Opens the directory at `path` in `dir`, which can be changed unless the
+/// host only allows it to be read.
+fn open_dir_at(dir: &Descriptor, path: &str) -> io::Result<Descriptor> {
+    let open = |flags| dir.open_at(PathFlags::SYMLINK_FOLLOW, path, OpenFlags::DIRECTORY, flags);
+    match open(DescriptorFlags::READ | DescriptorFlags::MUTATE_DIRECTORY) {
+        Err(ErrorCode::NotPermitted | ErrorCode::Access | ErrorCode::ReadOnly) => {
+            Ok(open(DescriptorFlags::READ)?)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Checks that `path` stays within the directory it's relative to, returning
+/// it as a string to pass to the host.
+fn check(path: &Path) -> io::Result<&str> {
+    check_from(path, 0).map(|(path, _)| path)
+}
+
+/// Checks that the target `original` of a symbolic link at `link` stays
+/// within the directory once resolved relative to the parent of `link`.
+fn check_target<'a>(original: &'a Path, link: &Path) -> io::Result<&'a str> {
+    let (_, depth) = check_from(link, 0)?;
+    check_from(original, depth.saturating_sub(1)).map(|(original, _)| original)
+}
+
+/// Checks that `path`, relative to a directory `depth` levels below the
+/// directory, stays within the directory, returning it as a string along
+/// with the depth it leads to.
+fn check_from(path: &Path, mut depth: usize) -> io::Result<(&str, usize)> {
+    let escapes = || {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("path `{}` leads outside of the directory", path.display()),
+        )
+    };
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return Err(escapes()),
+            Component::CurDir => {}
+            Component::ParentDir => depth = depth.checked_sub(1).ok_or_else(escapes)?,
+            Component::Normal(_) => depth += 1,
+        }
+    }
+    Ok((to_str(path)?, depth))
+}
+
+fn to_str(path: &Path) -> io::Result<&str> {
+    path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("path `{}` is not valid UTF-8", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind<T>(result: io::Result<T>) -> Option<io::ErrorKind> {
+        result.err().map(|e| e.kind())
+    }
+
+    #[test]
+    fn paths_within() {
+        assert_eq!(check(Path::new("a")).unwrap(), "a");
+        assert_eq!(check(Path::new("a/b/c.txt")).unwrap(), "a/b/c.txt");
+        assert_eq!(check(Path::new(".")).unwrap(), ".");
+        assert_eq!(check(Path::new("./a/../b")).unwrap(), "./a/../b");
+        assert_eq!(check(Path::new("a/b/../..")).unwrap(), "a/b/../..");
+    }
+
+    #[test]
+    fn paths_outside() {
+        let denied = Some(io::ErrorKind::PermissionDenied);
+        assert_eq!(kind(check(Path::new("/etc/passwd"))), denied);
+        assert_eq!(kind(check(Path::new("/"))), denied);
+        assert_eq!(kind(check(Path::new(".."))), denied);
+        assert_eq!(kind(check(Path::new("../a"))), denied);
+        assert_eq!(kind(check(Path::new("a/../../b"))), denied);
+        // Climbing back in doesn't help once outside.
+        assert_eq!(kind(check(Path::new("a/../../a/b"))), denied);
+    }
+
+    #[test]
+    fn symlink_targets() {
+        let target = |original, link| check_target(Path::new(original), Path::new(link));
+        assert_eq!(target("a", "link").unwrap(), "a");
+        assert_eq!(target("../a", "dir/link").unwrap(), "../a");
+        assert_eq!(target("../../a", "x/y/link").unwrap(), "../../a");
+        assert_eq!(target("./b/../a", "link").unwrap(), "./b/../a");
+
+        let denied = Some(io::ErrorKind::PermissionDenied);
+        assert_eq!(kind(target("/etc/passwd", "link")), denied);
+        assert_eq!(kind(target("../a", "link")), denied);
+        assert_eq!(kind(target("../../a", "dir/link")), denied);
+        assert_eq!(kind(target("../a", "dir/../link")), denied);
+        // The link itself is checked too.
+        assert_eq!(kind(target("a", "../link")), denied);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"a\xff"));
+        assert_eq!(kind(check(path)), Some(io::ErrorKind::InvalidInput));
+    }
+}
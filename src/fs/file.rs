@@ -0,0 +1,433 @@
+use super::preopens::with_resolved;
+use super::{stream_error, FileTimes, Metadata};
+use crate::filesystem::types::{Descriptor, DescriptorFlags, OpenFlags, PathFlags};
+use crate::io::streams::OutputStream;
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// The maximum number of bytes written to an append stream at once, which is
+/// the most `blocking-write-and-flush` accepts.
+const APPEND_CHUNK: usize = 4096;
+
+/// An open file, similar to [`std::fs::File`].
+///
+/// `wasi:filesystem` only has positional reads and writes, so the file keeps
+/// track of its own cursor which is advanced by [`Read`] and [`Write`] and
+/// moved with [`Seek`]. Files opened in append mode instead write through
+/// [`Descriptor::append_via_stream`], which always writes at the end of the
+/// file without moving the cursor.
+///
+/// ```no_run
+/// use std::io::{Read, Seek, SeekFrom, Write};
+/// use wasi::fs::File;
+///
+/// let mut file = File::create("/data/hello.txt")?;
+/// file.write_all(b"hello, world")?;
+/// file.seek(SeekFrom::Start(7))?;
+///
+/// let mut file = File::open("/data/hello.txt")?;
+/// let mut contents = String::new();
+/// file.read_to_string(&mut contents)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct File {
+    // NB: the append stream is a child of the descriptor so it's declared
+    // first to be dropped before it.
+    append: Option<OutputStream>,
+    descriptor: Descriptor,
+    cursor: Cell<u64>,
+}
+
+impl File {
+    /// Opens the file at `path` for reading.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Opens the file at `path` for writing, creating it if it doesn't exist
+    /// and truncating it if it does.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// Wraps an open descriptor, with the cursor at the start of the file.
+    ///
+    /// Writes go through the cursor even if `descriptor` was opened in append
+    /// mode.
+    pub fn from_descriptor(descriptor: Descriptor) -> File {
+        File {
+            append: None,
+            descriptor,
+            cursor: Cell::new(0),
+        }
+    }
+
+    /// Returns a new [`OpenOptions`] with every option disabled.
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    /// Returns the metadata of this file, including whether it was opened
+    /// read-only.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        Ok(Metadata::from_descriptor(&self.descriptor)?)
+    }
+
+    /// Reads bytes at `offset` into `buf` without moving the cursor,
+    /// returning how many bytes were read.
+    ///
+    /// Returns `Ok(0)` at the end of the file.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let len = u64::try_from(buf.len()).unwrap_or(u64::MAX);
+        let (data, _end) = self.descriptor.read(len, offset)?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    /// Writes bytes from `buf` at `offset` without moving the cursor,
+    /// returning how many bytes were written.
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let n = self.descriptor.write(buf, offset)?;
+        Ok(usize::try_from(n).unwrap_or(buf.len()))
+    }
+
+    /// Truncates or extends the file to `size` bytes.
+    ///
+    /// The cursor isn't moved, even if it ends up past the end of the file.
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        Ok(self.descriptor.set_size(size)?)
+    }
+
+    /// Sets the access and modification times of this file, leaving any
+    /// which aren't set in `times` unchanged.
+    pub fn set_times(&self, times: FileTimes) -> io::Result<()> {
+        let (accessed, modified) = times.to_new_timestamps()?;
+        Ok(self.descriptor.set_times(accessed, modified)?)
+    }
+
+    /// Sets the modification time of this file.
+    pub fn set_modified(&self, time: SystemTime) -> io::Result<()> {
+        self.set_times(FileTimes::new().set_modified(time))
+    }
+
+    /// Synchronizes the data and metadata of this file to storage.
+    pub fn sync_all(&self) -> io::Result<()> {
+        Ok(self.descriptor.sync()?)
+    }
+
+    /// Synchronizes the data of this file to storage, without necessarily
+    /// synchronizing its metadata.
+    pub fn sync_data(&self) -> io::Result<()> {
+        Ok(self.descriptor.sync_data()?)
+    }
+
+    /// Returns the underlying descriptor.
+    pub fn descriptor(&self) -> &Descriptor {
+        &self.descriptor
+    }
+
+    /// Consumes this file, returning the underlying descriptor.
+    pub fn into_descriptor(self) -> Descriptor {
+        let File {
+            append, descriptor, ..
+        } = self;
+        drop(append);
+        descriptor
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+}
+
+impl Read for &File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(buf, self.cursor.get())?;
+        self.cursor.set(self.cursor.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut &*self)
+    }
+}
+
+impl Write for &File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(stream) = &self.append {
+            let buf = &buf[..buf.len().min(APPEND_CHUNK)];
+            stream.blocking_write_and_flush(buf).map_err(stream_error)?;
+            return Ok(buf.len());
+        }
+        let n = self.write_at(buf, self.cursor.get())?;
+        self.cursor.set(self.cursor.get() + n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Positional writes aren't buffered, and neither are appends since
+        // each one is flushed as it's written.
+        Ok(())
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        Seek::seek(&mut &*self, pos)
+    }
+}
+
+impl Seek for &File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => {
+                self.cursor.set(n);
+                return Ok(n);
+            }
+            SeekFrom::End(offset) => (self.descriptor.stat()?.size, offset),
+            SeekFrom::Current(offset) => (self.cursor.get(), offset),
+        };
+        let cursor = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.cursor.set(cursor);
+        Ok(cursor)
+    }
+}
+
+/// Options for how a [`File`] is opened, similar to
+/// [`std::fs::OpenOptions`].
+///
+/// ```no_run
+/// use wasi::fs::OpenOptions;
+///
+/// let file = OpenOptions::new()
+///     .write(true)
+///     .create_new(true)
+///     .open("/data/new.txt")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// Creates a new set of options with every option disabled.
+    pub fn new() -> OpenOptions {
+        OpenOptions::default()
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut OpenOptions {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut OpenOptions {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for appending to the end of the file, which implies
+    /// write access.
+    ///
+    /// Every write of a file opened in append mode goes to the end of the
+    /// file, regardless of its cursor.
+    pub fn append(&mut self, append: bool) -> &mut OpenOptions {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option for truncating an existing file to a length of zero.
+    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option for creating the file if it doesn't exist.
+    pub fn create(&mut self, create: bool) -> &mut OpenOptions {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option for creating the file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Opens the file at `path` with these options.
+    ///
+    /// Like [`std::fs::OpenOptions::open`], creating or truncating a file
+    /// requires write or append access.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        with_resolved(path.as_ref(), |dir, path| self.open_at(dir, path))
+    }
+
+    /// Opens the file at `path` relative to `dir` with these options.
+    pub(super) fn open_at(&self, dir: &Descriptor, path: &str) -> io::Result<File> {
+        let (open_flags, flags) = self.flags()?;
+        let descriptor = dir.open_at(PathFlags::SYMLINK_FOLLOW, path, open_flags, flags)?;
+        let append = if self.append {
+            Some(descriptor.append_via_stream()?)
+        } else {
+            None
+        };
+        Ok(File {
+            append,
+            descriptor,
+            cursor: Cell::new(0),
+        })
+    }
+
+    /// Returns the flags to open a file with these options, checking that
+    /// they're consistent.
+    fn flags(&self) -> io::Result<(OpenFlags, DescriptorFlags)> {
+        let writable = self.write || self.append;
+        if (self.create || self.create_new || self.truncate) && !writable {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "creating or truncating a file requires write or append access",
+            ));
+        }
+        if self.truncate && self.append {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a file cannot be both truncated and appended to",
+            ));
+        }
+
+        let mut open_flags = OpenFlags::empty();
+        if self.create || self.create_new {
+            open_flags |= OpenFlags::CREATE;
+        }
+        if self.create_new {
+            open_flags |= OpenFlags::EXCLUSIVE;
+        }
+        if self.truncate {
+            open_flags |= OpenFlags::TRUNCATE;
+        }
+        let mut flags = DescriptorFlags::empty();
+        if self.read {
+            flags |= DescriptorFlags::READ;
+        }
+        if writable {
+            flags |= DescriptorFlags::WRITE;
+        }
+        Ok((open_flags, flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(options: &OpenOptions) -> (OpenFlags, DescriptorFlags) {
+        options.flags().unwrap()
+    }
+
+    #[test]
+    fn access_flags() {
+        let (open, access) = flags(OpenOptions::new().read(true));
+        assert_eq!(open, OpenFlags::empty());
+        assert_eq!(access, DescriptorFlags::READ);
+
+        let (open, access) = flags(OpenOptions::new().write(true));
+        assert_eq!(open, OpenFlags::empty());
+        assert_eq!(access, DescriptorFlags::WRITE);
+
+        let (_, access) = flags(OpenOptions::new().read(true).write(true));
+        assert_eq!(access, DescriptorFlags::READ | DescriptorFlags::WRITE);
+
+        // Append mode writes through a separate stream but still needs write
+        // access to the descriptor.
+        let (open, access) = flags(OpenOptions::new().append(true));
+        assert_eq!(open, OpenFlags::empty());
+        assert_eq!(access, DescriptorFlags::WRITE);
+    }
+
+    #[test]
+    fn open_flags() {
+        let cases = [
+            (
+                OpenOptions::new().write(true).create(true).clone(),
+                OpenFlags::CREATE,
+            ),
+            (
+                OpenOptions::new().write(true).create_new(true).clone(),
+                OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+            ),
+            (
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .create_new(true)
+                    .clone(),
+                OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+            ),
+            (
+                OpenOptions::new().write(true).truncate(true).clone(),
+                OpenFlags::TRUNCATE,
+            ),
+            (
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .clone(),
+                OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            ),
+            (
+                OpenOptions::new().append(true).create(true).clone(),
+                OpenFlags::CREATE,
+            ),
+        ];
+        for (options, expected) in cases {
+            assert_eq!(flags(&options).0, expected, "{options:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_options() {
+        let cases = [
+            OpenOptions::new().read(true).create(true).clone(),
+            OpenOptions::new().read(true).create_new(true).clone(),
+            OpenOptions::new().read(true).truncate(true).clone(),
+            OpenOptions::new().append(true).truncate(true).clone(),
+            OpenOptions::new()
+                .write(true)
+                .append(true)
+                .truncate(true)
+                .clone(),
+        ];
+        for options in cases {
+            let err = options.flags().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{options:?}");
+        }
+    }
+}
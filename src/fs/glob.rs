@@ -0,0 +1,1180 @@
+use super::preopens::with_resolved;
+use super::{DirEntry, FileType, ReadDir};
+use crate::filesystem::types::{Descriptor, DescriptorFlags, ErrorCode, OpenFlags, PathFlags};
+use std::format;
+use std::io;
+use std::iter::Map;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::string::String;
+use std::vec::Vec;
+
+/// Returns an iterator over the paths matching `pattern`, which are found
+/// lazily while iterating.
+///
+/// This is a shorthand for [`Glob::new`], see there for the syntax of
+/// patterns.
+///
+/// ```no_run
+/// for path in wasi::fs::glob("src/**/*.{rs,toml}")? {
+///     println!("{}", path?.display());
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn glob(pattern: &str) -> io::Result<Glob> {
+    Glob::new(pattern)
+}
+
+/// An iterator over the paths matching a glob pattern.
+///
+/// Patterns are split into components on `/`, and each component matches the
+/// name of one directory entry with the following syntax:
+///
+/// * `?` matches any single character.
+/// * `*` matches any sequence of characters.
+/// * `[abc]` matches one of the characters in the brackets, which may include
+///   ranges such as `[a-z]`, and `[!abc]` or `[^abc]` matches any other
+///   character.
+/// * `\` matches the character following it literally.
+///
+/// A component which is exactly `**` matches any number of nested
+/// directories, including none, and `{a,b}` matches either of the
+/// comma-separated alternatives, which may themselves contain `/` and other
+/// patterns.
+///
+/// Like in shells, wildcards don't match names starting with `.` unless the
+/// component starts with a literal `.`, and `**` doesn't descend into such
+/// directories nor follow symbolic links.
+///
+/// The leading components without wildcards are resolved against the
+/// preopened directories like every other function in this module, so only
+/// the directories below them are read. Every brace alternative is matched
+/// in the same walk, so each directory is read at most once and each path is
+/// yielded once.
+pub struct Glob {
+    walk: Walk<Root>,
+}
+
+enum Root {
+    Preopens,
+    Descriptor(Rc<Descriptor>),
+}
+
+/// The directory tree walked by a [`Glob`], which is only ever the host's
+/// outside of tests.
+trait Tree {
+    /// An open directory.
+    type Dir: Clone;
+    /// The names and types of the entries of a directory, other than `.` and
+    /// `..`.
+    type Entries: Iterator<Item = Result<(String, FileType), ErrorCode>>;
+
+    /// Opens the directory at `path`, which is `.` for the root of the tree.
+    ///
+    /// The outer error is returned if `path` can't even be looked up.
+    fn open_root(&self, path: &str) -> io::Result<Result<Self::Dir, ErrorCode>>;
+
+    /// Opens the directory `name` in `dir`, following symbolic links.
+    fn open(&self, dir: &Self::Dir, name: &str) -> Result<Self::Dir, ErrorCode>;
+
+    /// Checks that the entry `name` of `dir` exists, following symbolic
+    /// links.
+    fn stat(&self, dir: &Self::Dir, name: &str) -> Result<(), ErrorCode>;
+
+    /// Reads the entries of `dir`.
+    fn read(&self, dir: &Self::Dir) -> Result<Self::Entries, ErrorCode>;
+}
+
+/// The entries of a directory read from the host.
+type HostEntries =
+    Map<ReadDir, fn(Result<DirEntry, ErrorCode>) -> Result<(String, FileType), ErrorCode>>;
+
+impl Tree for Root {
+    type Dir = Rc<Descriptor>;
+    type Entries = HostEntries;
+
+    fn open_root(&self, path: &str) -> io::Result<Result<Rc<Descriptor>, ErrorCode>> {
+        let dir = match self {
+            Root::Preopens => with_resolved(Path::new(path), |dir, path| Ok(open_dir(dir, path)))?,
+            Root::Descriptor(dir) => open_dir(dir, path),
+        };
+        Ok(dir.map(Rc::new))
+    }
+
+    fn open(&self, dir: &Rc<Descriptor>, name: &str) -> Result<Rc<Descriptor>, ErrorCode> {
+        open_dir(dir, name).map(Rc::new)
+    }
+
+    fn stat(&self, dir: &Rc<Descriptor>, name: &str) -> Result<(), ErrorCode> {
+        dir.stat_at(PathFlags::SYMLINK_FOLLOW, name).map(drop)
+    }
+
+    fn read(&self, dir: &Rc<Descriptor>) -> Result<HostEntries, ErrorCode> {
+        let entries = ReadDir::from_rc(dir.clone(), PathBuf::new())?;
+        Ok(entries.map(|entry| entry.map(|entry| (entry.entry.name, entry.entry.type_.into()))))
+    }
+}
+
+/// The walks finding the paths matching a pattern in a [`Tree`].
+struct Walk<T: Tree> {
+    tree: T,
+    /// The components of every brace alternative of the pattern.
+    patterns: Vec<Vec<Component>>,
+    // Walks which haven't been started yet, in reverse order.
+    starts: Vec<Start>,
+    frames: Vec<Frame<T::Dir, T::Entries>>,
+}
+
+/// A position in the components of one of the patterns, which the entries
+/// of a directory are matched against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct State {
+    pattern: usize,
+    index: usize,
+}
+
+/// A walk starting in the directory named by the literal components
+/// `prefix`, shared by every pattern starting with them.
+struct Start {
+    absolute: bool,
+    prefix: Vec<String>,
+    states: Vec<State>,
+}
+
+/// A step of walking the directory tree.
+enum Frame<D, E> {
+    /// The directory `dir` at `path` still has to be matched against
+    /// `states`.
+    Dir {
+        dir: D,
+        path: PathBuf,
+        states: Vec<State>,
+    },
+    /// The entry `name` of `dir` at `path`, which only literal components
+    /// were matched against, still has to be looked up.
+    Name {
+        dir: D,
+        path: PathBuf,
+        name: String,
+        matched: bool,
+        next: Vec<State>,
+    },
+    /// The entries of the directory `dir` at `path` are being matched
+    /// against `states`.
+    Entries {
+        // NB: the entries may be a child of the directory so they're
+        // declared first to be dropped before it.
+        entries: E,
+        dir: D,
+        path: PathBuf,
+        states: Rc<[State]>,
+    },
+}
+
+impl Glob {
+    /// Returns an iterator over the paths matching `pattern`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `pattern` has an
+    /// unclosed `[` or `{`.
+    pub fn new(pattern: &str) -> io::Result<Glob> {
+        let walk = Walk::new(Root::Preopens, pattern)?;
+        Ok(Glob { walk })
+    }
+
+    /// Returns an iterator over the paths matching `pattern` within `dir`,
+    /// which are relative to `dir`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `pattern` is absolute.
+    /// Components of `..` are left to the host, which refuses to resolve them
+    /// outside of `dir`.
+    pub fn from_descriptor(dir: Descriptor, pattern: &str) -> io::Result<Glob> {
+        if pattern.starts_with('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("pattern `{pattern}` must be relative to the directory"),
+            ));
+        }
+        let walk = Walk::new(Root::Descriptor(Rc::new(dir)), pattern)?;
+        Ok(Glob { walk })
+    }
+}
+
+impl Iterator for Glob {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        self.walk.next()
+    }
+}
+
+impl<T: Tree> Walk<T> {
+    fn new(tree: T, pattern: &str) -> io::Result<Walk<T>> {
+        let (patterns, mut starts) = compile(pattern)?;
+        starts.reverse();
+        Ok(Walk {
+            tree,
+            patterns,
+            starts,
+            frames: Vec::new(),
+        })
+    }
+
+    /// Opens the directory named by the prefix of `start` and pushes a frame
+    /// to match its patterns.
+    fn start(&mut self, start: Start) -> io::Result<()> {
+        let mut prefix = start.prefix.join("/");
+        if start.absolute {
+            prefix.insert(0, '/');
+        }
+        let path = if prefix.is_empty() { "." } else { &prefix };
+        match self.tree.open_root(path)? {
+            Ok(dir) => {
+                let mut states = start.states;
+                closure(&self.patterns, &mut states);
+                self.frames.push(Frame::Dir {
+                    dir,
+                    path: PathBuf::from(prefix),
+                    states,
+                });
+                Ok(())
+            }
+            Err(code) if is_missing(code) => Ok(()),
+            Err(code) => Err(code.into()),
+        }
+    }
+
+    /// Matches the entries of `dir` against `states`, without reading the
+    /// directory if they're all literal components.
+    fn enter(&mut self, dir: T::Dir, path: PathBuf, states: Vec<State>) -> io::Result<()> {
+        let Some(names) = literal_names(&self.patterns, &states) else {
+            self.frames.push(Frame::Entries {
+                entries: self.tree.read(&dir)?,
+                dir,
+                path,
+                states: states.into(),
+            });
+            return Ok(());
+        };
+        for name in names.into_iter().rev() {
+            let (matched, next) = advance(&self.patterns, &states, name, None);
+            self.frames.push(Frame::Name {
+                dir: dir.clone(),
+                path: path.clone(),
+                name: name.into(),
+                matched,
+                next,
+            });
+        }
+        Ok(())
+    }
+
+    /// Looks up the entry `name` of `dir`, pushing a frame to walk it if it's
+    /// a directory `next` may match in, and returning its path if it exists
+    /// and was `matched`.
+    fn look_up(
+        &mut self,
+        dir: T::Dir,
+        path: PathBuf,
+        name: String,
+        matched: bool,
+        next: Vec<State>,
+    ) -> io::Result<Option<PathBuf>> {
+        let path = path.join(&name);
+        let mut exists = false;
+        if !next.is_empty() {
+            match self.tree.open(&dir, &name) {
+                Ok(sub) => {
+                    exists = true;
+                    self.frames.push(Frame::Dir {
+                        dir: sub,
+                        path: path.clone(),
+                        states: next,
+                    });
+                }
+                Err(code) if is_missing(code) => {}
+                Err(code) => return Err(code.into()),
+            }
+        }
+        if matched && !exists {
+            match self.tree.stat(&dir, &name) {
+                Ok(()) => exists = true,
+                Err(code) if is_missing(code) => {}
+                Err(code) => return Err(code.into()),
+            }
+        }
+        Ok((matched && exists).then_some(path))
+    }
+
+    /// Matches the entry `name` of `dir` at `path` against `states`, pushing
+    /// a frame to walk it if it's a directory which may contain matches, and
+    /// returning its path if it matches a whole pattern.
+    fn match_entry(
+        &mut self,
+        dir: &T::Dir,
+        path: &Path,
+        (name, file_type): (String, FileType),
+        states: &[State],
+    ) -> io::Result<Option<PathBuf>> {
+        let path = path.join(&name);
+        let (matched, next) = advance(&self.patterns, states, &name, Some(file_type));
+        if !next.is_empty() {
+            // Only wildcards and literals lead into symbolic links, which `**`
+            // doesn't follow, so they're always followed here.
+            match self.tree.open(dir, &name) {
+                Ok(sub) => self.frames.push(Frame::Dir {
+                    dir: sub,
+                    path: path.clone(),
+                    states: next,
+                }),
+                Err(code) if is_missing(code) => {}
+                Err(code) => return Err(code.into()),
+            }
+        }
+        Ok(matched.then_some(path))
+    }
+}
+
+impl<T: Tree> Iterator for Walk<T> {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        loop {
+            let found = match self.frames.pop() {
+                Some(Frame::Dir { dir, path, states }) => {
+                    self.enter(dir, path, states).map(|()| None)
+                }
+                Some(Frame::Name {
+                    dir,
+                    path,
+                    name,
+                    matched,
+                    next,
+                }) => self.look_up(dir, path, name, matched, next),
+                Some(Frame::Entries {
+                    mut entries,
+                    dir,
+                    path,
+                    states,
+                }) => {
+                    let entry = match entries.next() {
+                        Some(Ok(entry)) => entry,
+                        Some(Err(code)) => return Some(Err(code.into())),
+                        None => continue,
+                    };
+                    self.frames.push(Frame::Entries {
+                        entries,
+                        dir: dir.clone(),
+                        path: path.clone(),
+                        states: states.clone(),
+                    });
+                    self.match_entry(&dir, &path, entry, &states)
+                }
+                None => {
+                    let start = self.starts.pop()?;
+                    self.start(start).map(|()| None)
+                }
+            };
+            match found {
+                Ok(Some(path)) => return Some(Ok(path)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Returns whether an entry `name` of the given type matches a whole pattern
+/// from `states`, along with the states its own entries are matched against.
+///
+/// The type of the entry is `None` if it isn't known because the directory
+/// wasn't read, which only happens if `states` are all literal components.
+fn advance(
+    patterns: &[Vec<Component>],
+    states: &[State],
+    name: &str,
+    file_type: Option<FileType>,
+) -> (bool, Vec<State>) {
+    let mut matched = false;
+    let mut next = Vec::new();
+    let mut push = |state: State| {
+        if !next.contains(&state) {
+            next.push(state);
+        }
+    };
+    for &State { pattern, index } in states {
+        let components = &patterns[pattern];
+        let step = State {
+            pattern,
+            index: index + 1,
+        };
+        match &components[index] {
+            Component::Literal(literal) => {
+                if literal == name {
+                    push(step);
+                }
+            }
+            Component::Pattern(p) => {
+                if p.matches(name) {
+                    push(step);
+                }
+            }
+            Component::AnyDirs => {
+                if name.starts_with('.') {
+                    continue;
+                }
+                // `**` at the end of a pattern matches everything below the
+                // directory, and elsewhere only the directories leading to
+                // the rest of the pattern.
+                if step.index == components.len() {
+                    matched = true;
+                }
+                if file_type.is_some_and(|t| t.is_dir()) {
+                    push(State { pattern, index });
+                }
+            }
+        }
+    }
+    closure(patterns, &mut next);
+    next.retain(|state| {
+        let end = state.index == patterns[state.pattern].len();
+        matched |= end;
+        !end
+    });
+    if file_type.is_some_and(|t| !t.is_dir() && !t.is_symlink()) {
+        next.clear();
+    }
+    (matched, next)
+}
+
+/// Adds the states after every `**` in `states` which isn't last, since it
+/// matches no directories at all too.
+fn closure(patterns: &[Vec<Component>], states: &mut Vec<State>) {
+    let mut i = 0;
+    while let Some(&State { pattern, index }) = states.get(i) {
+        let components = &patterns[pattern];
+        if matches!(components.get(index), Some(Component::AnyDirs)) && index + 1 < components.len()
+        {
+            let skip = State {
+                pattern,
+                index: index + 1,
+            };
+            if !states.contains(&skip) {
+                states.push(skip);
+            }
+        }
+        i += 1;
+    }
+}
+
+/// Returns the names `states` match if they're all literal components, in
+/// which case the directory doesn't have to be read.
+fn literal_names<'a>(patterns: &'a [Vec<Component>], states: &[State]) -> Option<Vec<&'a str>> {
+    let mut names = Vec::new();
+    for state in states {
+        match &patterns[state.pattern][state.index] {
+            Component::Literal(name) => {
+                if !names.contains(&&name[..]) {
+                    names.push(&name[..]);
+                }
+            }
+            Component::Pattern(_) | Component::AnyDirs => return None,
+        }
+    }
+    Some(names)
+}
+
+/// Returns the leading literal components of a pattern, other than its last
+/// component, which are opened directly rather than matched.
+fn literal_prefix(components: &[Component]) -> Vec<String> {
+    components[..components.len() - 1]
+        .iter()
+        .map_while(|c| match c {
+            Component::Literal(name) => Some(name.clone()),
+            Component::Pattern(_) | Component::AnyDirs => None,
+        })
+        .collect()
+}
+
+/// Parses the components of every brace alternative of `pattern`, and groups
+/// them into the walks which find their matches.
+fn compile(pattern: &str) -> io::Result<(Vec<Vec<Component>>, Vec<Start>)> {
+    let mut expanded = Vec::new();
+    for pattern in expand_braces(pattern)? {
+        let components = pattern
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .map(Component::parse)
+            .collect::<io::Result<Vec<_>>>()?;
+        if !components.is_empty() {
+            expanded.push((pattern.starts_with('/'), components));
+        }
+    }
+    // Patterns are added to the walk of the shortest prefix of their own, so
+    // that no two walks can find the same path.
+    expanded.sort_by_cached_key(|(_, components)| literal_prefix(components).len());
+    let mut starts = Vec::<Start>::new();
+    let mut patterns = Vec::with_capacity(expanded.len());
+    for (pattern, (absolute, components)) in expanded.into_iter().enumerate() {
+        let prefix = literal_prefix(&components);
+        let start = starts.iter_mut().find(|start| {
+            start.absolute == absolute
+                && start.prefix.len() <= prefix.len()
+                && start.prefix.iter().zip(&prefix).all(|(a, b)| a == b)
+        });
+        match start {
+            Some(start) => start.states.push(State {
+                pattern,
+                index: start.prefix.len(),
+            }),
+            None => starts.push(Start {
+                absolute,
+                states: std::vec![State {
+                    pattern,
+                    index: prefix.len(),
+                }],
+                prefix,
+            }),
+        }
+        patterns.push(components);
+    }
+    Ok((patterns, starts))
+}
+
+fn open_dir(dir: &Descriptor, path: &str) -> Result<Descriptor, ErrorCode> {
+    dir.open_at(
+        PathFlags::SYMLINK_FOLLOW,
+        path,
+        OpenFlags::DIRECTORY,
+        DescriptorFlags::READ,
+    )
+}
+
+/// Returns whether `code` means a path doesn't exist or isn't a directory,
+/// which isn't an error while globbing but just no match.
+fn is_missing(code: ErrorCode) -> bool {
+    matches!(code, ErrorCode::NoEntry | ErrorCode::NotDirectory)
+}
+
+/// One component of a pattern, between two `/`.
+enum Component {
+    Literal(String),
+    Pattern(Pattern),
+    AnyDirs,
+}
+
+impl Component {
+    fn parse(component: &str) -> io::Result<Component> {
+        if component == "**" {
+            return Ok(Component::AnyDirs);
+        }
+        let pattern = Pattern::parse(component)?;
+        let literal = pattern
+            .tokens
+            .iter()
+            .map(|token| match token {
+                Token::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect::<Option<String>>();
+        Ok(match literal {
+            Some(literal) => Component::Literal(literal),
+            None => Component::Pattern(pattern),
+        })
+    }
+}
+
+struct Pattern {
+    tokens: Vec<Token>,
+}
+
+enum Token {
+    Char(char),
+    AnyChar,
+    AnyChars,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> io::Result<Pattern> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '?' => Token::AnyChar,
+                '*' => Token::AnyChars,
+                '\\' => Token::Char(chars.next().unwrap_or('\\')),
+                '[' => {
+                    let mut class = chars.clone();
+                    let negated = matches!(class.clone().next(), Some('!' | '^'));
+                    if negated {
+                        class.next();
+                    }
+                    let mut ranges = Vec::new();
+                    loop {
+                        let start = match class.next() {
+                            // A `]` first in the class is matched literally.
+                            Some(']') if !ranges.is_empty() => break,
+                            Some('\\') => class.next(),
+                            c => c,
+                        };
+                        let Some(start) = start else {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("unclosed `[` in pattern `{pattern}`"),
+                            ));
+                        };
+                        let mut end = start;
+                        let mut rest = class.clone();
+                        if let (Some('-'), Some(c)) = (rest.next(), rest.next()) {
+                            if c != ']' {
+                                end = c;
+                                class = rest;
+                            }
+                        }
+                        ranges.push((start, end));
+                    }
+                    chars = class;
+                    Token::Class { negated, ranges }
+                }
+                c => Token::Char(c),
+            });
+        }
+        Ok(Pattern { tokens })
+    }
+
+    /// Returns whether `name` matches this pattern, where wildcards don't
+    /// match a leading `.`.
+    fn matches(&self, name: &str) -> bool {
+        if name.starts_with('.') && !matches!(self.tokens.first(), Some(Token::Char('.'))) {
+            return false;
+        }
+        let name: Vec<char> = name.chars().collect();
+        let (mut t, mut n) = (0, 0);
+        // Where to resume after the last `*` if the rest doesn't match.
+        let mut backtrack = None;
+        while n < name.len() {
+            match self.tokens.get(t) {
+                Some(Token::AnyChars) => {
+                    backtrack = Some((t, n));
+                    t += 1;
+                    continue;
+                }
+                Some(token) if token.matches(name[n]) => {
+                    t += 1;
+                    n += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            match backtrack {
+                Some((star, matched)) => {
+                    t = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            }
+        }
+        self.tokens[t..]
+            .iter()
+            .all(|token| matches!(token, Token::AnyChars))
+    }
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(expected) => c == *expected,
+            Token::AnyChar => true,
+            Token::AnyChars => false,
+            Token::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&c))
+                    != *negated
+            }
+        }
+    }
+}
+
+/// Expands the brace alternatives of `pattern` into one pattern for every
+/// combination of them.
+fn expand_braces(pattern: &str) -> io::Result<Vec<String>> {
+    let Some((open, close, alternatives)) = find_braces(pattern)? else {
+        return Ok(std::vec![pattern.into()]);
+    };
+    let mut expanded = Vec::new();
+    for alternative in alternatives {
+        let pattern = format!("{}{alternative}{}", &pattern[..open], &pattern[close + 1..]);
+        expanded.extend(expand_braces(&pattern)?);
+    }
+    Ok(expanded)
+}
+
+/// Finds the first top-level `{...}` in `pattern`, returning the indices of
+/// both braces and the alternatives between them.
+fn find_braces(pattern: &str) -> io::Result<Option<(usize, usize, Vec<&str>)>> {
+    let mut open = None;
+    let mut depth = 0;
+    let mut alternatives = Vec::new();
+    let mut start = 0;
+    let mut chars = pattern.char_indices();
+    let mut in_class = false;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if !in_class => {
+                in_class = true;
+                // Like in `Pattern::parse`, a `]` first in the class doesn't
+                // close it.
+                let mut class = chars.clone();
+                if matches!(class.clone().next(), Some((_, '!' | '^'))) {
+                    class.next();
+                }
+                if matches!(class.next(), Some((_, ']'))) {
+                    chars = class;
+                }
+            }
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            ',' if depth == 1 => {
+                alternatives.push(&pattern[start..i]);
+                start = i + 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    alternatives.push(&pattern[start..i]);
+                    return Ok(open.map(|open| (open, i, alternatives)));
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unclosed `{{` in pattern `{pattern}`"),
+        ));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::types::DescriptorType;
+    use core::cell::RefCell;
+    use std::string::ToString;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(name)
+    }
+
+    /// An in-memory tree listing the paths of files and of directories
+    /// ending in `/`, where every ancestor of a listed path is a directory.
+    ///
+    /// Absolute paths are looked up like relative ones, and the directories
+    /// opened, read and looked up are recorded.
+    struct Mem {
+        paths: &'static [&'static str],
+        /// Paths which can't be accessed.
+        denied: &'static [&'static str],
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl Mem {
+        fn new(paths: &'static [&'static str]) -> Mem {
+            Mem {
+                paths,
+                denied: &[],
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn is_dir(&self, path: &str) -> bool {
+            path.is_empty()
+                || self.paths.iter().any(|p| {
+                    p.strip_prefix(path)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                })
+        }
+
+        fn lookup(&self, call: &str, path: String) -> Result<String, ErrorCode> {
+            self.calls.borrow_mut().push(format!("{call} {path}"));
+            if self.denied.contains(&&path[..]) {
+                Err(ErrorCode::Access)
+            } else if self.is_dir(&path) {
+                Ok(path)
+            } else if self.paths.contains(&&path[..]) {
+                Err(ErrorCode::NotDirectory)
+            } else {
+                Err(ErrorCode::NoEntry)
+            }
+        }
+
+        fn calls(&self, call: &str) -> Vec<String> {
+            let prefix = format!("{call} ");
+            let calls = self.calls.borrow();
+            let calls = calls.iter().filter_map(|c| c.strip_prefix(&prefix[..]));
+            calls.map(String::from).collect()
+        }
+    }
+
+    fn join(dir: &str, name: &str) -> String {
+        match dir {
+            "" => name.to_string(),
+            dir => format!("{dir}/{name}"),
+        }
+    }
+
+    impl Tree for &Mem {
+        type Dir = String;
+        type Entries = std::vec::IntoIter<Result<(String, FileType), ErrorCode>>;
+
+        fn open_root(&self, path: &str) -> io::Result<Result<String, ErrorCode>> {
+            let path = path.trim_start_matches('/');
+            let path = if path == "." { "" } else { path };
+            Ok(self.lookup("open", path.into()))
+        }
+
+        fn open(&self, dir: &String, name: &str) -> Result<String, ErrorCode> {
+            self.lookup("open", join(dir, name))
+        }
+
+        fn stat(&self, dir: &String, name: &str) -> Result<(), ErrorCode> {
+            match self.lookup("stat", join(dir, name)) {
+                Ok(_) | Err(ErrorCode::NotDirectory) => Ok(()),
+                Err(code) => Err(code),
+            }
+        }
+
+        fn read(&self, dir: &String) -> Result<Self::Entries, ErrorCode> {
+            self.calls.borrow_mut().push(format!("read {dir}"));
+            let mut entries = Vec::new();
+            for path in self.paths {
+                let rest = match &dir[..] {
+                    "" => Some(*path),
+                    dir => path.strip_prefix(dir).and_then(|p| p.strip_prefix('/')),
+                };
+                let Some(name) = rest.and_then(|rest| rest.split('/').next()) else {
+                    continue;
+                };
+                if name.is_empty() || entries.iter().any(|e: &(String, _)| e.0 == name) {
+                    continue;
+                }
+                let file_type = match self.is_dir(&join(dir, name)) {
+                    true => DescriptorType::Directory,
+                    false => DescriptorType::RegularFile,
+                };
+                entries.push((name.to_string(), file_type.into()));
+            }
+            Ok(entries.into_iter().map(Ok).collect::<Vec<_>>().into_iter())
+        }
+    }
+
+    /// Returns the paths matching `pattern` in `tree`, checking that none
+    /// is found twice and no directory is read twice.
+    fn walk(pattern: &str, tree: &Mem) -> io::Result<Vec<String>> {
+        let mut found = Vec::new();
+        for path in Walk::new(tree, pattern)? {
+            let path = path?.to_str().unwrap().to_string();
+            assert!(!found.contains(&path), "{path} found twice by {pattern}");
+            found.push(path);
+        }
+        let mut reads = tree.calls("read");
+        reads.sort();
+        reads.dedup();
+        assert_eq!(reads.len(), tree.calls("read").len(), "{pattern}");
+        found.sort();
+        Ok(found)
+    }
+
+    fn glob(pattern: &str, paths: &'static [&'static str]) -> Vec<String> {
+        walk(pattern, &Mem::new(paths)).unwrap()
+    }
+
+    const TREE: &[&str] = &[
+        "Cargo.toml",
+        "README.md",
+        ".gitignore",
+        ".github/workflows/main.yml",
+        "src/lib.rs",
+        "src/fs/mod.rs",
+        "src/fs/glob.rs",
+        "src/fs/.hidden.rs",
+        "src/.cache/x.rs",
+        "src/http/mod.rs",
+        "tests/a.rs",
+        "empty/",
+    ];
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", "abc"));
+        assert!(matches("*", ""));
+        assert!(matches("*.rs", "glob.rs"));
+        assert!(!matches("*.rs", "glob.rs.bak"));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(matches("a*b*c", "abcbc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(matches("?", "a"));
+        assert!(matches("?", "é"));
+        assert!(!matches("?", ""));
+        assert!(!matches("?", "ab"));
+        assert!(matches("a?c", "abc"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[a-z]", "q"));
+        assert!(!matches("[a-z]", "Q"));
+        assert!(matches("[a-cx]", "x"));
+        assert!(matches("[!x]", "y"));
+        assert!(!matches("[!x]", "x"));
+        assert!(matches("[^x]", "y"));
+        assert!(!matches("[^x]", "x"));
+        // A leading `]` is part of the class.
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[]a]", "a"));
+        assert!(matches("[!]]", "a"));
+        assert!(!matches("[!]]", "]"));
+        // So is a trailing `-`.
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[a-]", "a"));
+        assert!(!matches("[a-]", "b"));
+        assert!(matches("[\\]]", "]"));
+        assert_eq!(
+            Pattern::parse("[a-z").err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("\\?\\[", "?["));
+        assert!(matches("a\\", "a\\"));
+        assert!(matches!(
+            Component::parse("a\\*").unwrap(),
+            Component::Literal(literal) if literal == "a*"
+        ));
+        assert!(matches!(
+            Component::parse("a*").unwrap(),
+            Component::Pattern(_)
+        ));
+        assert!(matches!(
+            Component::parse("**").unwrap(),
+            Component::AnyDirs
+        ));
+    }
+
+    #[test]
+    fn dotfiles() {
+        assert!(!matches("*", ".gitignore"));
+        assert!(!matches("?gitignore", ".gitignore"));
+        assert!(!matches("[.]gitignore", ".gitignore"));
+        assert!(matches(".*", ".gitignore"));
+        assert!(matches("\\.*", ".gitignore"));
+        assert!(matches("*.*", "a.b"));
+        assert_eq!(
+            glob("*", TREE),
+            ["Cargo.toml", "README.md", "empty", "src", "tests"]
+        );
+        assert_eq!(glob(".*", TREE), [".github", ".gitignore"]);
+        assert_eq!(glob(".github/*/*", TREE), [".github/workflows/main.yml"]);
+        assert_eq!(
+            glob("src/**/*.rs", TREE),
+            [
+                "src/fs/glob.rs",
+                "src/fs/mod.rs",
+                "src/http/mod.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(glob("src/**/.*", TREE), ["src/.cache", "src/fs/.hidden.rs"]);
+    }
+
+    #[test]
+    fn braces() {
+        let expand = |pattern| expand_braces(pattern).unwrap();
+        assert_eq!(expand("a"), ["a"]);
+        assert_eq!(expand("{a,b}"), ["a", "b"]);
+        assert_eq!(expand("x{a,}y"), ["xay", "xy"]);
+        // Nested braces.
+        assert_eq!(expand("{a,{b,c}d}"), ["a", "bd", "cd"]);
+        // Adjacent braces.
+        assert_eq!(expand("{a,b}{c,d}"), ["ac", "ad", "bc", "bd"]);
+        assert_eq!(expand("{a/b,c}/*"), ["a/b/*", "c/*"]);
+        // Braces are literal when escaped or in a class.
+        assert_eq!(expand("\\{a,b}"), ["\\{a,b}"]);
+        assert_eq!(expand("[{]a,b}"), ["[{]a,b}"]);
+        assert_eq!(expand("[]{]{a,b}"), ["[]{]a", "[]{]b"]);
+        assert_eq!(find_braces("a}").unwrap(), None);
+        assert_eq!(
+            find_braces("{a,{b}").err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn any_dirs() {
+        // `**` matches no directories at all.
+        assert_eq!(glob("src/**/lib.rs", TREE), ["src/lib.rs"]);
+        // Or any number of them, but not hidden ones.
+        assert_eq!(
+            glob("src/**/mod.rs", TREE),
+            ["src/fs/mod.rs", "src/http/mod.rs"]
+        );
+        assert_eq!(glob("**/a.rs", TREE), ["tests/a.rs"]);
+        assert_eq!(glob("**/*.yml", TREE), Vec::<String>::new());
+        // At the end, it matches everything below the directory.
+        assert_eq!(
+            glob("src/**", TREE),
+            [
+                "src/fs",
+                "src/fs/glob.rs",
+                "src/fs/mod.rs",
+                "src/http",
+                "src/http/mod.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(glob("empty/**", TREE), Vec::<String>::new());
+        assert_eq!(glob("src/**/**/lib.rs", TREE), ["src/lib.rs"]);
+    }
+
+    #[test]
+    fn alternatives() {
+        assert_eq!(
+            glob("src/**/*.{rs,toml}", TREE),
+            [
+                "src/fs/glob.rs",
+                "src/fs/mod.rs",
+                "src/http/mod.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(
+            glob("{Cargo.toml,src/lib.rs,missing}", TREE),
+            ["Cargo.toml", "src/lib.rs"]
+        );
+        // Overlapping alternatives still find every path once.
+        assert_eq!(glob("{src,src}/lib.rs", TREE), ["src/lib.rs"]);
+        assert_eq!(
+            glob("{src/**/mod.rs,src/fs/*.rs}", TREE),
+            ["src/fs/glob.rs", "src/fs/mod.rs", "src/http/mod.rs"]
+        );
+        assert_eq!(glob("{*,src/*}.rs", TREE), ["src/lib.rs"]);
+    }
+
+    #[test]
+    fn literal_lookups() {
+        // Only literals are looked up, without reading any directory.
+        let tree = Mem::new(TREE);
+        let found = walk("{Cargo.toml,src/lib.rs,missing}", &tree).unwrap();
+        assert_eq!(found, ["Cargo.toml", "src/lib.rs"]);
+        assert_eq!(tree.calls("read"), Vec::<String>::new());
+        assert_eq!(tree.calls("open"), ["", "src"]);
+        assert_eq!(tree.calls("stat"), ["Cargo.toml", "missing", "src/lib.rs"]);
+
+        // A matched directory which is walked isn't looked up again.
+        let tree = Mem::new(TREE);
+        let found = walk("{src,src/lib.rs}", &tree).unwrap();
+        assert_eq!(found, ["src", "src/lib.rs"]);
+        assert_eq!(tree.calls("stat"), ["src/lib.rs"]);
+
+        // A matched file which can't be walked still is.
+        let tree = Mem::new(TREE);
+        let found = walk("{Cargo.toml,Cargo.toml/x}", &tree).unwrap();
+        assert_eq!(found, ["Cargo.toml"]);
+        assert_eq!(tree.calls("open"), ["", "Cargo.toml"]);
+        assert_eq!(tree.calls("stat"), ["Cargo.toml"]);
+
+        assert_eq!(glob("src/missing/lib.rs", TREE), Vec::<String>::new());
+        assert_eq!(glob("src/lib.rs/x", TREE), Vec::<String>::new());
+    }
+
+    #[test]
+    fn missing_prefixes() {
+        assert_eq!(glob("missing/*", TREE), Vec::<String>::new());
+        assert_eq!(glob("missing/more/*", TREE), Vec::<String>::new());
+        assert_eq!(glob("Cargo.toml/*", TREE), Vec::<String>::new());
+        assert_eq!(glob("empty/*", TREE), Vec::<String>::new());
+        // Other walks go on.
+        assert_eq!(glob("{missing,tests}/*", TREE), ["tests/a.rs"]);
+        assert_eq!(glob("/src/http/*", TREE), ["/src/http/mod.rs"]);
+
+        // Other errors are returned, whether opening a prefix or walking.
+        let tree = Mem {
+            denied: &["src"],
+            ..Mem::new(TREE)
+        };
+        let err = walk("src/*", &tree).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        let err = walk("*/lib.rs", &tree).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn no_duplicates() {
+        // `walk` checks that neither paths nor reads are repeated.
+        assert_eq!(glob("{src,src}/*.rs", TREE), ["src/lib.rs"]);
+        assert_eq!(
+            glob("{src/**,src/fs/*}", TREE),
+            [
+                "src/fs",
+                "src/fs/glob.rs",
+                "src/fs/mod.rs",
+                "src/http",
+                "src/http/mod.rs",
+                "src/lib.rs"
+            ]
+        );
+        assert_eq!(
+            glob("{**/*.rs,src/**/mod.rs,tests/*}", TREE),
+            [
+                "src/fs/glob.rs",
+                "src/fs/mod.rs",
+                "src/http/mod.rs",
+                "src/lib.rs",
+                "tests/a.rs"
+            ]
+        );
+        let tree = Mem::new(TREE);
+        walk("{*,src/*,src/fs/*,src/**}", &tree).unwrap();
+        let mut reads = tree.calls("read");
+        reads.sort();
+        assert_eq!(reads, ["", "src", "src/fs", "src/http"]);
+    }
+
+    #[test]
+    fn shared_walks() {
+        let (patterns, starts) = compile("src/{fs/*.rs,**/mod.rs,lib.rs}").unwrap();
+        assert_eq!(patterns.len(), 3);
+        assert_eq!(starts.len(), 1);
+        assert_eq!(starts[0].prefix, ["src"]);
+        let (_, starts) = compile("{/a/*,/b/*,c/*}").unwrap();
+        let prefixes = starts
+            .iter()
+            .map(|start| (start.absolute, start.prefix.join("/")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            prefixes,
+            [(true, "a".into()), (true, "b".into()), (false, "c".into())]
+        );
+    }
+}
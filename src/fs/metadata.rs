@@ -0,0 +1,319 @@
+use crate::clocks::wall_clock::Datetime;
+use crate::filesystem::types::{
+    Descriptor, DescriptorFlags, DescriptorStat, DescriptorType, ErrorCode, NewTimestamp,
+};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Metadata about a file or directory, similar to [`std::fs::Metadata`].
+///
+/// Returned by [`metadata`](super::metadata) and
+/// [`File::metadata`](super::File::metadata).
+///
+/// ```no_run
+/// let metadata = wasi::fs::metadata("/data/hello.txt")?;
+/// println!("{} bytes, modified {:?}", metadata.len(), metadata.modified()?);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    stat: DescriptorStat,
+    flags: Option<DescriptorFlags>,
+}
+
+impl Metadata {
+    /// Returns the metadata of the file or directory open as `descriptor`,
+    /// including the flags it was opened with.
+    pub fn from_descriptor(descriptor: &Descriptor) -> Result<Metadata, ErrorCode> {
+        Ok(Metadata {
+            stat: descriptor.stat()?,
+            flags: Some(descriptor.get_flags()?),
+        })
+    }
+
+    /// Returns the type of this file.
+    pub fn file_type(&self) -> FileType {
+        FileType(self.stat.type_)
+    }
+
+    /// Returns whether this metadata is for a directory.
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    /// Returns whether this metadata is for a regular file.
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    /// Returns whether this metadata is for a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.stat.size
+    }
+
+    /// Returns whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the time the file was last modified.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the host doesn't keep
+    /// track of modification times.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        to_system_time(self.stat.data_modification_timestamp, "modification")
+    }
+
+    /// Returns the time the file was last accessed.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the host doesn't keep
+    /// track of access times.
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        to_system_time(self.stat.data_access_timestamp, "access")
+    }
+
+    /// Returns the time the status of the file, such as its permissions or
+    /// links, was last changed.
+    ///
+    /// `wasi:filesystem` doesn't report creation times, so this is the
+    /// closest equivalent of [`std::fs::Metadata::created`].
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] if the host doesn't keep
+    /// track of status change times.
+    pub fn changed(&self) -> io::Result<SystemTime> {
+        to_system_time(self.stat.status_change_timestamp, "status change")
+    }
+
+    /// Returns whether the file was opened without write access, based on
+    /// the [`DescriptorFlags`] of its descriptor.
+    ///
+    /// Returns `None` for metadata which wasn't read from an open
+    /// descriptor, such as that returned by [`metadata`](super::metadata),
+    /// since `wasi:filesystem` has no permission bits.
+    pub fn readonly(&self) -> Option<bool> {
+        self.flags
+            .map(|flags| !flags.contains(DescriptorFlags::WRITE))
+    }
+
+    /// Returns the underlying stat returned by the host.
+    pub fn stat(&self) -> &DescriptorStat {
+        &self.stat
+    }
+}
+
+impl From<DescriptorStat> for Metadata {
+    fn from(stat: DescriptorStat) -> Metadata {
+        Metadata { stat, flags: None }
+    }
+}
+
+/// Converts a time reported by the host, failing with
+/// [`io::ErrorKind::InvalidData`] instead of panicking if it can't be
+/// represented as a [`SystemTime`].
+fn to_system_time(datetime: Option<Datetime>, what: &str) -> io::Result<SystemTime> {
+    let Some(datetime) = datetime else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            std::format!("{what} time is not available on this host"),
+        ));
+    };
+    // The host may report nanoseconds beyond a whole second, which
+    // `Duration::new` would carry into the seconds, panicking on overflow.
+    Duration::from_secs(datetime.seconds)
+        .checked_add(Duration::from_nanos(datetime.nanoseconds.into()))
+        .and_then(|since_epoch| UNIX_EPOCH.checked_add(since_epoch))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                std::format!("{what} time reported by the host is out of range"),
+            )
+        })
+}
+
+/// The type of a file, similar to [`std::fs::FileType`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileType(DescriptorType);
+
+impl FileType {
+    /// Returns whether this is the type of a directory.
+    pub fn is_dir(&self) -> bool {
+        self.0 == DescriptorType::Directory
+    }
+
+    /// Returns whether this is the type of a regular file.
+    pub fn is_file(&self) -> bool {
+        self.0 == DescriptorType::RegularFile
+    }
+
+    /// Returns whether this is the type of a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.0 == DescriptorType::SymbolicLink
+    }
+
+    /// Returns the underlying descriptor type.
+    pub fn descriptor_type(&self) -> DescriptorType {
+        self.0
+    }
+}
+
+impl From<DescriptorType> for FileType {
+    fn from(type_: DescriptorType) -> FileType {
+        FileType(type_)
+    }
+}
+
+/// Timestamps to set on a file with [`set_times`](super::set_times) or
+/// [`File::set_times`](super::File::set_times), similar to
+/// [`std::fs::FileTimes`].
+///
+/// Timestamps which aren't set are left unchanged.
+///
+/// ```no_run
+/// use std::time::SystemTime;
+/// use wasi::fs::FileTimes;
+///
+/// let times = FileTimes::new().set_modified(SystemTime::UNIX_EPOCH).set_accessed_now();
+/// wasi::fs::set_times("/data/hello.txt", times)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileTimes {
+    accessed: Option<Time>,
+    modified: Option<Time>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Time {
+    Now,
+    At(SystemTime),
+}
+
+impl FileTimes {
+    /// Creates a new set of times which leaves every timestamp unchanged.
+    pub fn new() -> FileTimes {
+        FileTimes::default()
+    }
+
+    /// Sets the time the file was last accessed.
+    pub fn set_accessed(mut self, time: SystemTime) -> FileTimes {
+        self.accessed = Some(Time::At(time));
+        self
+    }
+
+    /// Sets the time the file was last accessed to the current time of the
+    /// host's filesystem clock.
+    pub fn set_accessed_now(mut self) -> FileTimes {
+        self.accessed = Some(Time::Now);
+        self
+    }
+
+    /// Sets the time the file was last modified.
+    pub fn set_modified(mut self, time: SystemTime) -> FileTimes {
+        self.modified = Some(Time::At(time));
+        self
+    }
+
+    /// Sets the time the file was last modified to the current time of the
+    /// host's filesystem clock.
+    pub fn set_modified_now(mut self) -> FileTimes {
+        self.modified = Some(Time::Now);
+        self
+    }
+
+    /// Returns the access and modification timestamps to pass to
+    /// `set-times` or `set-times-at`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] for times before the Unix
+    /// epoch, which can't be represented by `wasi:clocks`.
+    pub fn to_new_timestamps(&self) -> io::Result<(NewTimestamp, NewTimestamp)> {
+        Ok((
+            to_new_timestamp(self.accessed)?,
+            to_new_timestamp(self.modified)?,
+        ))
+    }
+}
+
+fn to_new_timestamp(time: Option<Time>) -> io::Result<NewTimestamp> {
+    Ok(match time {
+        None => NewTimestamp::NoChange,
+        Some(Time::Now) => NewTimestamp::Now,
+        Some(Time::At(time)) => {
+            let since_epoch = time.duration_since(UNIX_EPOCH).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot set a file time before the Unix epoch",
+                )
+            })?;
+            NewTimestamp::Timestamp(Datetime {
+                seconds: since_epoch.as_secs(),
+                nanoseconds: since_epoch.subsec_nanos(),
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(seconds: u64, nanoseconds: u32) -> io::Result<SystemTime> {
+        to_system_time(
+            Some(Datetime {
+                seconds,
+                nanoseconds,
+            }),
+            "test",
+        )
+    }
+
+    #[test]
+    fn system_times() {
+        assert_eq!(time(0, 0).unwrap(), UNIX_EPOCH);
+        assert_eq!(
+            time(1_700_000_000, 5).unwrap(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 5)
+        );
+        // Whole seconds in the nanoseconds are carried over.
+        assert_eq!(
+            time(1, 2_500_000_000).unwrap(),
+            UNIX_EPOCH + Duration::from_millis(3_500)
+        );
+    }
+
+    #[test]
+    fn out_of_range_system_times() {
+        for (seconds, nanoseconds) in [(u64::MAX, 0), (u64::MAX, u32::MAX)] {
+            let err = time(seconds, nanoseconds).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let err = to_system_time(None, "test").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn new_timestamps() {
+        let time = UNIX_EPOCH + Duration::new(10, 20);
+        let times = FileTimes::new().set_modified(time);
+        let (accessed, modified) = times.to_new_timestamps().unwrap();
+        assert!(matches!(accessed, NewTimestamp::NoChange));
+        assert!(matches!(
+            modified,
+            NewTimestamp::Timestamp(Datetime {
+                seconds: 10,
+                nanoseconds: 20
+            })
+        ));
+
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(1);
+        let err = FileTimes::new()
+            .set_accessed(before_epoch)
+            .to_new_timestamps()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
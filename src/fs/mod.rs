@@ -0,0 +1,226 @@
+//! Filesystem manipulation in the style of [`std::fs`], built on
+//! [`wasi:filesystem`].
+//!
+//! [`wasi:filesystem`]: https://github.com/WebAssembly/wasi-filesystem
+//!
+//! Components can only access the directories preopened for them by the host,
+//! and every operation of `wasi:filesystem` is relative to a [`Descriptor`].
+//! The functions in this module instead take ordinary paths and resolve them
+//! against the preopen with the longest name matching the path, as returned
+//! by [`get_directories`](crate::filesystem::preopens::get_directories).
+//! Relative paths are resolved against the initial working directory from
+//! [`initial_cwd`](crate::cli::environment::initial_cwd) if there is one.
+//!
+//! ```no_run
+//! use wasi::fs;
+//!
+//! fs::create_dir_all("/data/logs")?;
+//! fs::write("/data/logs/today.txt", "hello")?;
+//! assert_eq!(fs::read_to_string("/data/logs/today.txt")?, "hello");
+//!
+//! for entry in fs::read_dir("/data/logs")? {
+//!     println!("{}", entry?.path().display());
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::filesystem::types::{
+    filesystem_error_code, Descriptor, DescriptorFlags, DescriptorType, ErrorCode, OpenFlags,
+    PathFlags,
+};
+use crate::io::streams::StreamError;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+
+mod copy;
+mod dir;
+mod file;
+mod glob;
+mod metadata;
+mod preopens;
+mod read_dir;
+mod temp;
+mod walk_dir;
+
+pub use self::copy::{copy_dir_all, copy_file, move_path, remove_dir_all};
+pub use self::dir::Dir;
+pub use self::file::{File, OpenOptions};
+pub use self::glob::{glob, Glob};
+pub use self::metadata::{FileTimes, FileType, Metadata};
+pub use self::read_dir::{DirEntry, ReadDir};
+pub use self::temp::{write_atomic, NamedTempFile, TempDir};
+pub use self::walk_dir::{WalkDir, WalkDirIter, WalkEntry};
+
+use self::preopens::with_resolved;
+
+/// Reads the entire contents of the file at `path`.
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut contents = Vec::with_capacity(usize::try_from(size).unwrap_or(0));
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// Reads the entire contents of the file at `path` into a string.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the contents aren't valid
+/// UTF-8.
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes `contents` to the file at `path`, creating it if it doesn't exist
+/// and replacing its contents if it does.
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    File::create(path)?.write_all(contents.as_ref())
+}
+
+/// Creates a new, empty directory at `path`.
+///
+/// Fails if the parent of `path` doesn't exist or `path` already exists.
+pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.create_directory_at(path).map_err(io::Error::from)
+    })
+}
+
+/// Creates the directory at `path` along with any of its parents which don't
+/// exist yet.
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    with_resolved(path.as_ref(), |dir, path| {
+        if path == "." {
+            return Ok(());
+        }
+        let mut prefix = String::with_capacity(path.len());
+        for component in path.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(component);
+            match dir.create_directory_at(&prefix) {
+                Ok(()) => {}
+                Err(ErrorCode::Exist) if is_dir_at(dir, &prefix) => {}
+                Err(code) => return Err(code.into()),
+            }
+        }
+        Ok(())
+    })
+}
+
+fn is_dir_at(dir: &Descriptor, path: &str) -> bool {
+    dir.stat_at(PathFlags::SYMLINK_FOLLOW, path)
+        .is_ok_and(|stat| stat.type_ == DescriptorType::Directory)
+}
+
+/// Removes the file at `path`.
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.unlink_file_at(path).map_err(io::Error::from)
+    })
+}
+
+/// Removes the empty directory at `path`.
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.remove_directory_at(path).map_err(io::Error::from)
+    })
+}
+
+/// Renames the file or directory at `from` to `to`, replacing `to` if it
+/// already exists.
+///
+/// Both paths may be in different preopened directories, in which case the
+/// host decides whether the rename is possible.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    with_resolved(from.as_ref(), |from_dir, from| {
+        with_resolved(to.as_ref(), |to_dir, to| {
+            from_dir
+                .rename_at(from, to_dir, to)
+                .map_err(io::Error::from)
+        })
+    })
+}
+
+/// Returns the metadata of the file or directory at `path`, following
+/// symbolic links.
+pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.stat_at(PathFlags::SYMLINK_FOLLOW, path)
+            .map(Metadata::from)
+            .map_err(io::Error::from)
+    })
+}
+
+/// Returns the metadata of the file or directory at `path` without following
+/// symbolic links.
+pub fn symlink_metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.stat_at(PathFlags::empty(), path)
+            .map(Metadata::from)
+            .map_err(io::Error::from)
+    })
+}
+
+/// Sets the access and modification times of the file or directory at
+/// `path`, following symbolic links.
+///
+/// Times which aren't set in `times` are left unchanged.
+pub fn set_times<P: AsRef<Path>>(path: P, times: FileTimes) -> io::Result<()> {
+    let (accessed, modified) = times.to_new_timestamps()?;
+    with_resolved(path.as_ref(), |dir, path| {
+        dir.set_times_at(PathFlags::SYMLINK_FOLLOW, path, accessed, modified)
+            .map_err(io::Error::from)
+    })
+}
+
+/// Returns an iterator over the entries of the directory at `path`.
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
+    let path = path.as_ref();
+    let dir = with_resolved(path, |dir, path| {
+        dir.open_at(
+            PathFlags::SYMLINK_FOLLOW,
+            path,
+            OpenFlags::DIRECTORY,
+            DescriptorFlags::READ,
+        )
+        .map_err(io::Error::from)
+    })?;
+    Ok(ReadDir::new(dir, path)?)
+}
+
+/// Converts a [`StreamError`] of a file's stream into an [`io::Error`],
+/// recovering the filesystem error code behind it, which
+/// `From<StreamError> for io::Error` doesn't look up.
+///
+/// This is used by [`File`] and [`copy_file`], and is useful for streams
+/// created directly from a [`Descriptor`], such as with
+/// [`Descriptor::read_via_stream`].
+///
+/// ```no_run
+/// use std::io::Read;
+/// # use wasi::filesystem::types::Descriptor;
+///
+/// # fn read(file: &Descriptor) -> std::io::Result<Vec<u8>> {
+/// let stream = file.read_via_stream(0).map_err(std::io::Error::from)?;
+/// let mut contents = Vec::new();
+/// loop {
+///     match stream.blocking_read(64 * 1024) {
+///         Ok(chunk) => contents.extend(chunk),
+///         Err(wasi::io::streams::StreamError::Closed) => return Ok(contents),
+///         Err(e) => return Err(wasi::fs::stream_error(e)),
+///     }
+/// }
+/// # }
+/// ```
+pub fn stream_error(err: StreamError) -> io::Error {
+    match err {
+        StreamError::Closed => io::Error::from(io::ErrorKind::BrokenPipe),
+        StreamError::LastOperationFailed(e) => match filesystem_error_code(&e) {
+            Some(code) => code.into(),
+            None => io::Error::other(e.to_debug_string()),
+        },
+    }
+}
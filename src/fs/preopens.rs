@@ -0,0 +1,273 @@
+//! Resolution of paths against the preopened directories of the component.
+
+use crate::cli::environment::initial_cwd;
+use crate::filesystem::preopens::get_directories;
+use crate::filesystem::types::Descriptor;
+use std::format;
+use std::io;
+use std::path::Path;
+use std::string::String;
+use std::thread_local;
+use std::vec::Vec;
+
+struct Preopen {
+    descriptor: Descriptor,
+    absolute: bool,
+    components: Vec<String>,
+}
+
+thread_local! {
+    // Preopens and the initial working directory never change over the
+    // lifetime of a component, so they're only queried from the host once.
+    static PREOPENS: Vec<Preopen> = get_directories()
+        .into_iter()
+        .map(|(descriptor, name)| {
+            let (absolute, components) = normalize(&name);
+            Preopen {
+                descriptor,
+                absolute,
+                components,
+            }
+        })
+        .collect();
+    static CWD: Option<String> = initial_cwd();
+}
+
+/// Resolves `path` to the preopened directory with the longest name matching
+/// it and calls `f` with that directory and the rest of the path relative to
+/// it.
+///
+/// Relative paths are first joined onto the initial working directory, if
+/// there is one. `..` components within the preopened directory are left
+/// for the host to resolve, so that they step out of symbolic links the same
+/// way they do elsewhere. Only paths whose `..` components would climb above
+/// the directory they start in are resolved lexically instead, as in
+/// `/data/../logs` when both are preopened, since the host can't resolve
+/// those across preopens.
+pub(crate) fn with_resolved<T>(
+    path: &Path,
+    f: impl FnOnce(&Descriptor, &str) -> io::Result<T>,
+) -> io::Result<T> {
+    let path_str = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("path `{}` is not valid UTF-8", path.display()),
+        )
+    })?;
+    CWD.with(|cwd| {
+        PREOPENS.with(|preopens| {
+            let joined = match cwd {
+                Some(cwd) if !path_str.starts_with('/') => Some(format!("{cwd}/{path_str}")),
+                _ => None,
+            };
+            let resolved = joined
+                .iter()
+                .map(|path| &path[..])
+                .chain([path_str])
+                .find_map(|path| resolve(preopens, |p| (p.absolute, &p.components), path));
+            match resolved {
+                Some((preopen, rest)) => f(&preopen.descriptor, &rest),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "path `{}` is not within a preopened directory",
+                        path.display()
+                    ),
+                )),
+            }
+        })
+    })
+}
+
+/// Finds the preopen with the longest name, as returned by `name`, which
+/// `path` is within, returning it along with the rest of the path.
+fn resolve<'a, P>(
+    preopens: &'a [P],
+    name: impl Fn(&P) -> (bool, &[String]),
+    path: &str,
+) -> Option<(&'a P, String)> {
+    for (absolute, components) in [split(path), normalize(path)] {
+        let mut candidates = preopens
+            .iter()
+            .filter(|p| {
+                let (a, prefix) = name(p);
+                a == absolute && is_prefix(prefix, &components)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|p| core::cmp::Reverse(name(p).1.len()));
+        for preopen in candidates {
+            let rest = &components[name(preopen).1.len()..];
+            if !stays_within(rest) {
+                continue;
+            }
+            let rest = if rest.is_empty() {
+                String::from(".")
+            } else {
+                rest.join("/")
+            };
+            return Some((preopen, rest));
+        }
+    }
+    None
+}
+
+/// Splits `path` into whether it's absolute and its components other than
+/// `.`, leaving `..` as is.
+fn split(path: &str) -> (bool, Vec<String>) {
+    let components = path
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .map(String::from)
+        .collect();
+    (path.starts_with('/'), components)
+}
+
+/// Splits `path` into whether it's absolute and its components, with `.` and
+/// `..` resolved lexically.
+fn normalize(path: &str) -> (bool, Vec<String>) {
+    let absolute = path.starts_with('/');
+    let mut components: Vec<String> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => match components.last() {
+                Some(last) if last != ".." => {
+                    components.pop();
+                }
+                // `..` of the root is the root itself.
+                _ if absolute => {}
+                _ => components.push(component.into()),
+            },
+            _ => components.push(component.into()),
+        }
+    }
+    (absolute, components)
+}
+
+fn is_prefix(prefix: &[String], components: &[String]) -> bool {
+    prefix.len() <= components.len() && prefix.iter().zip(components).all(|(a, b)| a == b)
+}
+
+/// Returns whether the relative path `components` never climbs above the
+/// directory it starts in.
+fn stays_within(components: &[String]) -> bool {
+    let mut depth = 0usize;
+    components.iter().all(|c| {
+        if c == ".." {
+            depth.checked_sub(1).map(|d| depth = d).is_some()
+        } else {
+            depth += 1;
+            true
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn components(components: &[&str]) -> Vec<String> {
+        components.iter().map(|c| c.to_string()).collect()
+    }
+
+    /// Resolves `path` against preopens with the given names, returning the
+    /// name of the chosen preopen and the rest of the path.
+    fn resolve_in<'a>(names: &[&'a str], path: &str) -> Option<(&'a str, String)> {
+        let preopens = names
+            .iter()
+            .map(|name| (*name, normalize(name)))
+            .collect::<Vec<_>>();
+        resolve(&preopens, |(_, (a, c))| (*a, &c[..]), path).map(|(p, rest)| (p.0, rest))
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize("/"), (true, components(&[])));
+        assert_eq!(normalize(""), (false, components(&[])));
+        assert_eq!(normalize("."), (false, components(&[])));
+        assert_eq!(
+            normalize("/a//b/./c/"),
+            (true, components(&["a", "b", "c"]))
+        );
+        assert_eq!(normalize("/a/b/../c"), (true, components(&["a", "c"])));
+        assert_eq!(normalize("/../a"), (true, components(&["a"])));
+        assert_eq!(normalize("a/../../b"), (false, components(&["..", "b"])));
+        assert_eq!(
+            normalize("../../a"),
+            (false, components(&["..", "..", "a"]))
+        );
+    }
+
+    #[test]
+    fn split_paths() {
+        assert_eq!(
+            split("/a/./b/../c"),
+            (true, components(&["a", "b", "..", "c"]))
+        );
+        assert_eq!(split("./a//"), (false, components(&["a"])));
+    }
+
+    #[test]
+    fn prefixes() {
+        assert!(is_prefix(&components(&[]), &components(&[])));
+        assert!(is_prefix(&components(&[]), &components(&["a"])));
+        assert!(is_prefix(&components(&["a"]), &components(&["a", "b"])));
+        assert!(is_prefix(
+            &components(&["a", "b"]),
+            &components(&["a", "b"])
+        ));
+        assert!(!is_prefix(&components(&["a", "b"]), &components(&["a"])));
+        assert!(!is_prefix(&components(&["a"]), &components(&["ab"])));
+        assert!(!is_prefix(
+            &components(&["a", "b"]),
+            &components(&["a", "c"])
+        ));
+    }
+
+    #[test]
+    fn longest_preopen() {
+        let preopens = ["/", "/data", "/data/logs", "tmp", "."];
+        let resolve = |path| resolve_in(&preopens, path);
+        assert_eq!(resolve("/etc/passwd"), Some(("/", "etc/passwd".into())));
+        assert_eq!(resolve("/data"), Some(("/data", ".".into())));
+        assert_eq!(resolve("/data/x"), Some(("/data", "x".into())));
+        assert_eq!(resolve("/data/logs/a"), Some(("/data/logs", "a".into())));
+        assert_eq!(resolve("/data/logsx"), Some(("/data", "logsx".into())));
+        assert_eq!(resolve("tmp/a"), Some(("tmp", "a".into())));
+        assert_eq!(resolve("other"), Some((".", "other".into())));
+        assert_eq!(resolve_in(&["/data"], "/etc"), None);
+        assert_eq!(resolve_in(&["/data"], "data"), None);
+    }
+
+    #[test]
+    fn dot_dot_within_preopen() {
+        // `..` which stays within the preopen is left for the host, since
+        // `link` may be a symbolic link.
+        let preopens = ["/", "/data"];
+        assert_eq!(
+            resolve_in(&preopens, "/data/link/../x"),
+            Some(("/data", "link/../x".into()))
+        );
+        assert_eq!(
+            resolve_in(&["/data", "/data/logs"], "/data/logs/../x"),
+            Some(("/data", "logs/../x".into()))
+        );
+        assert_eq!(
+            resolve_in(&preopens, "/data/./a/./../b"),
+            Some(("/data", "a/../b".into()))
+        );
+    }
+
+    #[test]
+    fn dot_dot_above_preopen() {
+        let preopens = ["/data", "/logs", "tmp"];
+        let resolve = |path| resolve_in(&preopens, path);
+        assert_eq!(resolve("/data/../logs/a"), Some(("/logs", "a".into())));
+        assert_eq!(resolve("/data/a/../../logs"), Some(("/logs", ".".into())));
+        assert_eq!(resolve("/data/../data/a"), Some(("/data", "a".into())));
+        assert_eq!(resolve("/data/../etc"), None);
+        assert_eq!(resolve("tmp/../../x"), None);
+        assert_eq!(resolve_in(&["."], "../x"), None);
+    }
+}
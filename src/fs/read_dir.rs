@@ -0,0 +1,112 @@
+use super::{FileType, Metadata};
+use crate::filesystem::types::{
+    Descriptor, DirectoryEntry, DirectoryEntryStream, ErrorCode, PathFlags,
+};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// An iterator over the entries of a directory, returned from
+/// [`read_dir`](super::read_dir).
+///
+/// The entries `.` and `..` are skipped if the host returns them. Errors are
+/// reported as the [`ErrorCode`] returned by the host, which converts into an
+/// [`std::io::Error`] with `?`.
+///
+/// ```no_run
+/// for entry in wasi::fs::read_dir("/data")? {
+///     let entry = entry?;
+///     if entry.file_type().is_file() {
+///         println!("{}: {} bytes", entry.name(), entry.metadata()?.len());
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ReadDir {
+    // NB: the stream is a child of the directory so it's declared first to be
+    // dropped before it.
+    stream: DirectoryEntryStream,
+    pub(super) dir: Rc<Descriptor>,
+    path: PathBuf,
+}
+
+impl ReadDir {
+    /// Returns an iterator over the entries of `dir`, whose paths are joined
+    /// onto `path`.
+    pub fn new(dir: Descriptor, path: impl Into<PathBuf>) -> Result<ReadDir, ErrorCode> {
+        ReadDir::from_rc(Rc::new(dir), path.into())
+    }
+
+    /// Like [`ReadDir::new`], for a directory which is shared with others.
+    pub(super) fn from_rc(dir: Rc<Descriptor>, path: PathBuf) -> Result<ReadDir, ErrorCode> {
+        let stream = dir.read_directory()?;
+        Ok(ReadDir { stream, dir, path })
+    }
+
+    /// Returns the path of the directory being read.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry, ErrorCode>;
+
+    fn next(&mut self) -> Option<Result<DirEntry, ErrorCode>> {
+        loop {
+            let entry = match self.stream.read_directory_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return None,
+                Err(code) => return Some(Err(code)),
+            };
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            return Some(Ok(DirEntry {
+                dir: self.dir.clone(),
+                path: self.path.join(&entry.name),
+                entry,
+            }));
+        }
+    }
+}
+
+/// An entry of a directory, returned from [`ReadDir`].
+#[derive(Debug)]
+pub struct DirEntry {
+    pub(super) dir: Rc<Descriptor>,
+    pub(super) path: PathBuf,
+    pub(super) entry: DirectoryEntry,
+}
+
+impl DirEntry {
+    /// Returns the path of this entry, joined onto the path of the directory
+    /// it was read from.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// Returns the name of this entry within its directory.
+    pub fn name(&self) -> &str {
+        &self.entry.name
+    }
+
+    /// Returns the type of this entry, as reported by the directory listing.
+    pub fn file_type(&self) -> FileType {
+        self.entry.type_.into()
+    }
+
+    /// Returns the metadata of this entry, without following symbolic links.
+    ///
+    /// The metadata is queried from the host on every call with `stat-at`
+    /// relative to the directory this entry was read from.
+    pub fn metadata(&self) -> Result<Metadata, ErrorCode> {
+        self.dir
+            .stat_at(PathFlags::empty(), &self.entry.name)
+            .map(Metadata::from)
+    }
+
+    /// Returns the directory this entry was read from.
+    pub fn dir(&self) -> &Descriptor {
+        &self.dir
+    }
+}
@@ -0,0 +1,326 @@
+use super::copy::remove_dir_all_at;
+use super::preopens::with_resolved;
+use super::File;
+use crate::filesystem::types::{Descriptor, DescriptorFlags, ErrorCode, OpenFlags, PathFlags};
+use crate::random::random::get_random_u64;
+use std::format;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::string::String;
+
+/// How many random names are tried before giving up on creating a temporary
+/// file or directory.
+const ATTEMPTS: usize = 16;
+
+/// A file with a unique, random name which is removed when dropped, similar to
+/// the `tempfile` crate.
+///
+/// The file is created next to where it's going to end up, so that it can be
+/// [persisted](NamedTempFile::persist) with a rename instead of a copy. The
+/// name is picked with [`get_random_u64`] and the file is created with
+/// [`OpenFlags::EXCLUSIVE`], so it's never shared with anyone else.
+///
+/// ```no_run
+/// use std::io::Write;
+/// use wasi::fs::NamedTempFile;
+///
+/// let mut file = NamedTempFile::new_in("/data")?;
+/// file.write_all(b"hello")?;
+/// file.as_file().sync_all()?;
+/// file.persist("/data/hello.txt")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct NamedTempFile {
+    // Taken when the file is persisted.
+    file: Option<File>,
+    parent: Descriptor,
+    name: String,
+    path: PathBuf,
+}
+
+impl NamedTempFile {
+    /// Creates a new temporary file in the directory at `dir`, opened for
+    /// reading and writing.
+    pub fn new_in<P: AsRef<Path>>(dir: P) -> io::Result<NamedTempFile> {
+        let dir = dir.as_ref();
+        let parent = open_dir(dir)?;
+        let (name, descriptor) = create_unique(get_random_u64, |name| {
+            parent.open_at(
+                PathFlags::empty(),
+                name,
+                OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                DescriptorFlags::READ | DescriptorFlags::WRITE,
+            )
+        })?;
+        Ok(NamedTempFile {
+            file: Some(File::from_descriptor(descriptor)),
+            path: dir.join(&name),
+            parent,
+            name,
+        })
+    }
+
+    /// Returns the path of this file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the open file.
+    pub fn as_file(&self) -> &File {
+        self.file.as_ref().unwrap()
+    }
+
+    /// Returns the open file mutably.
+    pub fn as_file_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+
+    /// Renames this file to `path`, replacing anything already there, and
+    /// returns the open file without removing it.
+    ///
+    /// `path` should be in the same directory as this file for the rename to
+    /// be atomic. On failure the temporary file is removed when dropped.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> io::Result<File> {
+        with_resolved(path.as_ref(), |dir, path| {
+            self.parent
+                .rename_at(&self.name, dir, path)
+                .map_err(io::Error::from)
+        })?;
+        Ok(self.file.take().unwrap())
+    }
+
+    /// Closes and removes this file, returning any error which is ignored
+    /// when it's dropped instead.
+    pub fn close(mut self) -> io::Result<()> {
+        drop(self.file.take());
+        Ok(self.parent.unlink_file_at(&self.name)?)
+    }
+}
+
+impl Drop for NamedTempFile {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            drop(file);
+            let _ = self.parent.unlink_file_at(&self.name);
+        }
+    }
+}
+
+impl Read for NamedTempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_file_mut().read(buf)
+    }
+}
+
+impl Write for NamedTempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.as_file_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.as_file_mut().flush()
+    }
+}
+
+impl Seek for NamedTempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.as_file_mut().seek(pos)
+    }
+}
+
+/// A directory with a unique, random name which is removed along with its
+/// contents when dropped, similar to the `tempfile` crate.
+///
+/// ```no_run
+/// use wasi::fs::{self, TempDir};
+///
+/// let dir = TempDir::new_in("/data")?;
+/// fs::write(dir.path().join("scratch.txt"), "hello")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct TempDir {
+    parent: Descriptor,
+    name: String,
+    path: PathBuf,
+    // Cleared when the directory is removed with `close`.
+    remove: bool,
+}
+
+impl TempDir {
+    /// Creates a new temporary directory in the directory at `dir`.
+    pub fn new_in<P: AsRef<Path>>(dir: P) -> io::Result<TempDir> {
+        let dir = dir.as_ref();
+        let parent = open_dir(dir)?;
+        let (name, ()) = create_unique(get_random_u64, |name| parent.create_directory_at(name))?;
+        Ok(TempDir {
+            path: dir.join(&name),
+            parent,
+            name,
+            remove: true,
+        })
+    }
+
+    /// Returns the path of this directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes this directory and its contents, returning any error which is
+    /// ignored when it's dropped instead.
+    pub fn close(mut self) -> io::Result<()> {
+        self.remove = false;
+        remove_dir_all_at(&self.parent, &self.name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if self.remove {
+            let _ = remove_dir_all_at(&self.parent, &self.name);
+        }
+    }
+}
+
+/// Writes `contents` to the file at `path` atomically, so that the file
+/// either keeps its old contents or has all of the new ones even if the
+/// component or host crashes.
+///
+/// The contents are written to a [`NamedTempFile`] next to `path` and synced
+/// to storage. The file is then renamed over `path`, and the directory is
+/// synced so that the rename is stored too. Hosts which can't sync
+/// directories report [`ErrorCode::Unsupported`], which is ignored, and the
+/// rename may then be lost in a crash, leaving the old contents in place.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    let path = path.as_ref();
+    let invalid = |reason| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("path `{}` {reason}", path.display()),
+        )
+    };
+    let dir = path
+        .parent()
+        .ok_or_else(|| invalid("has no parent directory"))?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| invalid("has no file name"))?
+        .to_str()
+        .ok_or_else(|| invalid("is not valid UTF-8"))?;
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(contents.as_ref())?;
+    file.as_file().sync_all()?;
+    file.parent.rename_at(&file.name, &file.parent, name)?;
+    // The file has been renamed, so it mustn't be removed when dropped.
+    drop(file.file.take());
+    match file.parent.sync() {
+        Ok(()) | Err(ErrorCode::Unsupported) => Ok(()),
+        Err(code) => Err(code.into()),
+    }
+}
+
+fn open_dir(path: &Path) -> io::Result<Descriptor> {
+    with_resolved(path, |dir, path| {
+        dir.open_at(
+            PathFlags::SYMLINK_FOLLOW,
+            path,
+            OpenFlags::DIRECTORY,
+            DescriptorFlags::READ | DescriptorFlags::MUTATE_DIRECTORY,
+        )
+        .map_err(io::Error::from)
+    })
+}
+
+/// Calls `create` with names made from `random` until it doesn't fail with
+/// [`ErrorCode::Exist`].
+fn create_unique<T>(
+    mut random: impl FnMut() -> u64,
+    mut create: impl FnMut(&str) -> Result<T, ErrorCode>,
+) -> io::Result<(String, T)> {
+    for _ in 0..ATTEMPTS {
+        let name = format!(".tmp{:016x}", random());
+        match create(&name) {
+            Ok(value) => return Ok((name, value)),
+            Err(ErrorCode::Exist) => continue,
+            Err(code) => return Err(code.into()),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "too many temporary files exist with the same name",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn names() {
+        for (random, expected) in [
+            (0, ".tmp0000000000000000"),
+            (0xabc, ".tmp0000000000000abc"),
+            (0x0123_4567_89ab_cdef, ".tmp0123456789abcdef"),
+            (u64::MAX, ".tmpffffffffffffffff"),
+        ] {
+            let (name, ()) = create_unique(|| random, |_| Ok(())).unwrap();
+            assert_eq!(name, expected);
+        }
+    }
+
+    #[test]
+    fn existing_names() {
+        let mut randoms = 1..;
+        let mut tried = Vec::new();
+        let (name, value) = create_unique(
+            || randoms.next().unwrap(),
+            |name| {
+                tried.push(String::from(name));
+                if tried.len() < 3 {
+                    Err(ErrorCode::Exist)
+                } else {
+                    Ok(tried.len())
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(name, ".tmp0000000000000003");
+        assert_eq!(value, 3);
+        assert_eq!(
+            tried,
+            [
+                ".tmp0000000000000001",
+                ".tmp0000000000000002",
+                name.as_str()
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_attempts() {
+        let mut attempts = 0;
+        let err = create_unique(
+            || 0,
+            |_| -> Result<(), _> {
+                attempts += 1;
+                Err(ErrorCode::Exist)
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(attempts, ATTEMPTS);
+
+        // Other errors are returned straight away.
+        attempts = 0;
+        let err = create_unique(
+            || 0,
+            |_| -> Result<(), _> {
+                attempts += 1;
+                Err(ErrorCode::Access)
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(attempts, 1);
+    }
+}
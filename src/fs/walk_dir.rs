@@ -0,0 +1,514 @@
+use super::preopens::with_resolved;
+use super::{FileType, Metadata, ReadDir};
+use crate::filesystem::types::{
+    Descriptor, DescriptorFlags, DescriptorType, ErrorCode, MetadataHashValue, OpenFlags, PathFlags,
+};
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::string::String;
+use std::vec::Vec;
+
+/// A recursive iterator over a directory tree, similar to the `walkdir`
+/// crate.
+///
+/// The root directory itself is yielded first at depth 0, followed by its
+/// entries at depth 1 and so on. Entries are yielded depth-first by default,
+/// with each directory yielded before its contents.
+///
+/// ```no_run
+/// use wasi::fs::WalkDir;
+///
+/// let sources = WalkDir::new("/src")
+///     .max_depth(8)
+///     .filter_entry(|entry| entry.name() != "target");
+/// for entry in sources {
+///     let entry = entry?;
+///     if entry.file_type().is_file() && entry.name().ends_with(".rs") {
+///         println!("{}", entry.path().display());
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct WalkDir {
+    // Taken when the walk starts.
+    root: Option<Root>,
+    min_depth: usize,
+    max_depth: usize,
+    follow_links: bool,
+    breadth_first: bool,
+    filter: Option<Filter>,
+}
+
+type Filter = Box<dyn FnMut(&WalkEntry) -> bool>;
+
+enum Root {
+    Path(PathBuf),
+    Descriptor(Descriptor, PathBuf),
+}
+
+impl WalkDir {
+    /// Creates a walker over the directory at `path`, resolved against the
+    /// preopened directories like every other function in this module.
+    pub fn new<P: AsRef<Path>>(path: P) -> WalkDir {
+        WalkDir::with_root(Root::Path(path.as_ref().to_path_buf()))
+    }
+
+    /// Creates a walker over the directory `dir`, whose entries' paths are
+    /// joined onto `path`.
+    ///
+    /// This never looks outside of `dir`, except by following symbolic links
+    /// if [`follow_links`](WalkDir::follow_links) is enabled and the host
+    /// allows it.
+    pub fn from_descriptor<P: AsRef<Path>>(dir: Descriptor, path: P) -> WalkDir {
+        WalkDir::with_root(Root::Descriptor(dir, path.as_ref().to_path_buf()))
+    }
+
+    fn with_root(root: Root) -> WalkDir {
+        WalkDir {
+            root: Some(root),
+            min_depth: 0,
+            max_depth: usize::MAX,
+            follow_links: false,
+            breadth_first: false,
+            filter: None,
+        }
+    }
+
+    /// Sets the minimum depth of entries to yield, where the root is at depth
+    /// 0. Entries above this depth are still walked, but not yielded.
+    pub fn min_depth(mut self, depth: usize) -> WalkDir {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Sets the maximum depth of entries to yield, where the root is at depth
+    /// 0. Directories at this depth are yielded, but not walked.
+    pub fn max_depth(mut self, depth: usize) -> WalkDir {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets whether symbolic links are followed, which is disabled by
+    /// default.
+    ///
+    /// When enabled, links are reported with the type of their target and
+    /// links to directories are walked. A link to one of its own ancestors
+    /// is reported as an [`ErrorCode::Loop`] error instead of being walked.
+    pub fn follow_links(mut self, follow: bool) -> WalkDir {
+        self.follow_links = follow;
+        self
+    }
+
+    /// Sets whether entries are yielded breadth-first, yielding every entry
+    /// at one depth before any entry at the next, instead of depth-first.
+    pub fn breadth_first(mut self, breadth_first: bool) -> WalkDir {
+        self.breadth_first = breadth_first;
+        self
+    }
+
+    /// Only yields entries for which `predicate` returns `true`.
+    ///
+    /// Directories for which `predicate` returns `false` aren't walked
+    /// either, so this can be used to prune whole subtrees. The root is
+    /// always yielded.
+    pub fn filter_entry<F>(mut self, predicate: F) -> WalkDir
+    where
+        F: FnMut(&WalkEntry) -> bool + 'static,
+    {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+}
+
+impl IntoIterator for WalkDir {
+    type Item = io::Result<WalkEntry>;
+    type IntoIter = WalkDirIter;
+
+    fn into_iter(self) -> WalkDirIter {
+        WalkDirIter {
+            options: self,
+            levels: Vec::new(),
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+}
+
+/// The iterator returned by [`WalkDir::into_iter`].
+///
+/// Walking depth-first keeps one directory open for every level between the
+/// root and the current entry. Walking breadth-first only keeps the root and
+/// the directory being read open, and queues the paths of the directories to
+/// walk next.
+///
+/// Either way, a directory which can't be opened is yielded first and the
+/// error is returned by the following call, like the `walkdir` crate does.
+/// Walking breadth-first, directories are opened once to check them when
+/// they're yielded and again when they're read.
+pub struct WalkDirIter {
+    options: WalkDir,
+    levels: Vec<Level>,
+    pending: VecDeque<Pending>,
+    /// The error opening the directory which was yielded last.
+    error: Option<io::Error>,
+}
+
+/// A directory being read.
+struct Level {
+    entries: ReadDir,
+    /// The root of the walk.
+    root: Rc<Descriptor>,
+    depth: usize,
+    /// The path of the directory relative to the root, or `.` for the root.
+    relative: String,
+    ancestors: Option<Rc<Ancestor>>,
+}
+
+/// A directory to be read, at `relative` in `root`.
+///
+/// Its `ancestors` include the directory itself once it has been checked
+/// against them.
+struct Pending {
+    root: Rc<Descriptor>,
+    relative: String,
+    path: PathBuf,
+    depth: usize,
+    ancestors: Option<Rc<Ancestor>>,
+}
+
+/// A directory above the one being read, which is compared against to
+/// detect loops when following symbolic links.
+struct Ancestor {
+    hash: MetadataHashValue,
+    parent: Option<Rc<Ancestor>>,
+}
+
+impl WalkDirIter {
+    fn start(&mut self, root: Root) -> io::Result<Option<WalkEntry>> {
+        let (dir, path) = match root {
+            Root::Path(path) => {
+                let dir = with_resolved(&path, |dir, path| {
+                    Ok(dir.open_at(
+                        PathFlags::SYMLINK_FOLLOW,
+                        path,
+                        OpenFlags::DIRECTORY,
+                        DescriptorFlags::READ,
+                    )?)
+                })?;
+                (dir, path)
+            }
+            Root::Descriptor(dir, path) => (dir, path),
+        };
+        let entries = ReadDir::new(dir, path.clone())?;
+        let root = WalkEntry {
+            dir: entries.dir.clone(),
+            name: String::from("."),
+            path,
+            depth: 0,
+            file_type: DescriptorType::Directory.into(),
+            follow_links: self.options.follow_links,
+        };
+        if self.options.walks(0) {
+            let ancestors = ancestors(&entries.dir, None, self.options.follow_links)?;
+            self.levels.push(Level {
+                root: entries.dir.clone(),
+                entries,
+                depth: 0,
+                relative: String::from("."),
+                ancestors,
+            });
+        }
+        Ok(self.options.yields(0).then_some(root))
+    }
+
+    /// Opens the directory `entry` and checks it against its ancestors, then
+    /// reads it next when walking depth-first or queues it as `pending` when
+    /// walking breadth-first.
+    fn walk(&mut self, entry: &WalkEntry, mut pending: Pending) -> io::Result<()> {
+        let follow_links = self.options.follow_links;
+        let dir = open_dir(&entry.dir, &entry.name, follow_links)?;
+        pending.ancestors = ancestors(&dir, pending.ancestors, follow_links)?;
+        if self.options.breadth_first {
+            self.pending.push_back(pending);
+        } else {
+            self.levels.push(Level::new(dir, pending)?);
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for WalkDirIter {
+    type Item = io::Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<io::Result<WalkEntry>> {
+        if let Some(root) = self.options.root.take() {
+            match self.start(root) {
+                Ok(Some(root)) => return Some(Ok(root)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        let follow_links = self.options.follow_links;
+        loop {
+            // Walking breadth-first, the next pending directory is only
+            // opened once the previous one has been read.
+            if self.levels.is_empty() {
+                let pending = self.pending.pop_front()?;
+                let level = open_dir(&pending.root, &pending.relative, follow_links)
+                    .and_then(|dir| Level::new(dir, pending));
+                match level {
+                    Ok(level) => self.levels.push(level),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            let level = self.levels.last_mut()?;
+            let entry = match level.entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(code)) => return Some(Err(code.into())),
+                None => {
+                    self.levels.pop();
+                    continue;
+                }
+            };
+            let depth = level.depth + 1;
+
+            let mut file_type = entry.file_type();
+            if file_type.is_symlink() && follow_links {
+                match entry
+                    .dir
+                    .stat_at(PathFlags::SYMLINK_FOLLOW, &entry.entry.name)
+                {
+                    Ok(stat) => file_type = stat.type_.into(),
+                    Err(code) => return Some(Err(code.into())),
+                }
+            }
+            // Details of the level are only copied for entries which are
+            // going to be walked.
+            let pending = (file_type.is_dir() && self.options.walks(depth)).then(|| Pending {
+                root: level.root.clone(),
+                relative: match &level.relative[..] {
+                    "." => entry.entry.name.clone(),
+                    parent => std::format!("{parent}/{}", entry.entry.name),
+                },
+                path: entry.path.clone(),
+                depth,
+                ancestors: level.ancestors.clone(),
+            });
+            let entry = WalkEntry {
+                dir: entry.dir,
+                name: entry.entry.name,
+                path: entry.path,
+                depth,
+                file_type,
+                follow_links,
+            };
+
+            if let Some(filter) = &mut self.options.filter {
+                if !filter(&entry) {
+                    continue;
+                }
+            }
+            if let Some(pending) = pending {
+                if let Err(e) = self.walk(&entry, pending) {
+                    if !self.options.yields(depth) {
+                        return Some(Err(e));
+                    }
+                    self.error = Some(e);
+                }
+            }
+            if self.options.yields(depth) {
+                return Some(Ok(entry));
+            }
+        }
+    }
+}
+
+impl WalkDir {
+    /// Returns whether entries at `depth` are yielded.
+    fn yields(&self, depth: usize) -> bool {
+        depth >= self.min_depth
+    }
+
+    /// Returns whether directories at `depth` are walked.
+    fn walks(&self, depth: usize) -> bool {
+        depth < self.max_depth
+    }
+}
+
+impl Level {
+    /// Reads `dir` as `pending`, once it has been checked against its
+    /// ancestors.
+    fn new(dir: Descriptor, pending: Pending) -> io::Result<Level> {
+        Ok(Level {
+            entries: ReadDir::new(dir, pending.path)?,
+            root: pending.root,
+            depth: pending.depth,
+            relative: pending.relative,
+            ancestors: pending.ancestors,
+        })
+    }
+}
+
+/// Opens the directory `name` in `dir`.
+fn open_dir(dir: &Descriptor, name: &str, follow_links: bool) -> io::Result<Descriptor> {
+    let path_flags = if follow_links {
+        PathFlags::SYMLINK_FOLLOW
+    } else {
+        PathFlags::empty()
+    };
+    Ok(dir.open_at(
+        path_flags,
+        name,
+        OpenFlags::DIRECTORY,
+        DescriptorFlags::READ,
+    )?)
+}
+
+/// Returns the ancestors of the entries of `dir`, failing if `dir` is
+/// already one of `parent`.
+///
+/// Loops can only happen through symbolic links, so nothing is tracked unless
+/// links are being followed. Directories are compared by their
+/// [`metadata_hash`](Descriptor::metadata_hash) so that their ancestors don't
+/// have to be kept open.
+fn ancestors(
+    dir: &Descriptor,
+    parent: Option<Rc<Ancestor>>,
+    follow_links: bool,
+) -> io::Result<Option<Rc<Ancestor>>> {
+    if !follow_links {
+        return Ok(None);
+    }
+    let hash = dir.metadata_hash()?;
+    if is_ancestor(parent.as_deref(), &hash) {
+        return Err(ErrorCode::Loop.into());
+    }
+    Ok(Some(Rc::new(Ancestor { hash, parent })))
+}
+
+/// Returns whether the directory with `hash` is `parent` or one of its
+/// ancestors.
+fn is_ancestor(parent: Option<&Ancestor>, hash: &MetadataHashValue) -> bool {
+    let mut next = parent;
+    while let Some(ancestor) = next {
+        if ancestor.hash.lower == hash.lower && ancestor.hash.upper == hash.upper {
+            return true;
+        }
+        next = ancestor.parent.as_deref();
+    }
+    false
+}
+
+/// An entry yielded by [`WalkDir`].
+#[derive(Debug)]
+pub struct WalkEntry {
+    dir: Rc<Descriptor>,
+    name: String,
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    follow_links: bool,
+}
+
+impl WalkEntry {
+    /// Returns the path of this entry, joined onto the path of the root.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the name of this entry within its directory, or `.` for the
+    /// root.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the depth of this entry, where the root is at depth 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the type of this entry, which is the type of the target of a
+    /// symbolic link if links are being followed.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// Returns the metadata of this entry, following symbolic links if links
+    /// are being followed.
+    pub fn metadata(&self) -> Result<Metadata, ErrorCode> {
+        let path_flags = if self.follow_links {
+            PathFlags::SYMLINK_FOLLOW
+        } else {
+            PathFlags::empty()
+        };
+        self.dir.stat_at(path_flags, &self.name).map(Metadata::from)
+    }
+
+    /// Returns the directory containing this entry, or the root directory
+    /// itself for the root.
+    pub fn dir(&self) -> &Descriptor {
+        &self.dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_limits() {
+        let all: &[usize] = &[0, 1, 2, 3, 4, 5];
+        let cases: [(usize, usize, &[usize], &[usize]); 5] = [
+            (0, usize::MAX, all, all),
+            (2, usize::MAX, &[2, 3, 4, 5], all),
+            (0, 2, all, &[0, 1]),
+            (1, 3, &[1, 2, 3, 4, 5], &[0, 1, 2]),
+            // The root is yielded but nothing is read.
+            (0, 0, all, &[]),
+        ];
+        for (min, max, yielded, walked) in cases {
+            let options = WalkDir::new("/").min_depth(min).max_depth(max);
+            let depths = all.iter().copied();
+            let actual: Vec<usize> = depths.clone().filter(|&d| options.yields(d)).collect();
+            assert_eq!(actual, yielded, "yielded by {min}..={max}");
+            let actual: Vec<usize> = depths.filter(|&d| options.walks(d)).collect();
+            assert_eq!(actual, walked, "walked by {min}..={max}");
+        }
+    }
+
+    fn hash(lower: u64) -> MetadataHashValue {
+        MetadataHashValue { lower, upper: 7 }
+    }
+
+    fn chain(hashes: &[u64]) -> Option<Rc<Ancestor>> {
+        hashes.iter().fold(None, |parent, &lower| {
+            Some(Rc::new(Ancestor {
+                hash: hash(lower),
+                parent,
+            }))
+        })
+    }
+
+    #[test]
+    fn loops() {
+        // The chain from the root `1` down to the current directory `3`.
+        let ancestors = chain(&[1, 2, 3]);
+        let parent = ancestors.as_deref();
+        assert!(is_ancestor(parent, &hash(1)));
+        assert!(is_ancestor(parent, &hash(2)));
+        assert!(is_ancestor(parent, &hash(3)));
+        assert!(!is_ancestor(parent, &hash(4)));
+        // Both halves of the hash are compared.
+        let other = MetadataHashValue { lower: 1, upper: 8 };
+        assert!(!is_ancestor(parent, &other));
+        assert!(!is_ancestor(None, &hash(1)));
+    }
+}
@@ -0,0 +1,624 @@
+//! Helpers for reading the bodies of incoming requests and responses, and
+//! writing the bodies of outgoing ones.
+
+#[cfg(feature = "json")]
+use crate::http::response::Response;
+#[cfg(feature = "json")]
+use crate::http::types::StatusCode;
+use crate::http::types::{
+    http_error_code, ErrorCode, Fields, FutureTrailers, IncomingBody, InputStream, OutgoingBody,
+    OutgoingRequest, OutgoingResponse, OutputStream, Trailers,
+};
+use crate::io::blocking::{copy_chunk, write_len};
+use crate::io::streams::StreamError;
+use crate::runtime::{self, WaitFor};
+#[cfg(feature = "json")]
+use std::fmt;
+use std::format;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+#[cfg(feature = "json")]
+use std::string::ToString;
+use std::task::{ready, Context, Poll};
+use std::vec::Vec;
+
+/// A reader over the contents of an [`IncomingBody`].
+///
+/// The body's [`InputStream`] is a child resource which must be dropped
+/// before the body itself, and this type takes care of dropping the two in
+/// the correct order. Once the contents have been read, the body can be
+/// finished with [`trailers`](BodyReader::trailers) to receive its trailers.
+///
+/// The contents can be read either blocking, through [`io::Read`], or
+/// asynchronously on the [`runtime`](crate::runtime) executor through
+/// [`read`](BodyReader::read).
+///
+/// ```no_run
+/// use std::io::Read;
+/// use wasi::http::body::BodyReader;
+/// # use wasi::http::types::IncomingRequest;
+///
+/// # fn handle(request: IncomingRequest) -> std::io::Result<()> {
+/// let body = request.consume().unwrap();
+/// let mut reader = BodyReader::new(body)?.limit(1 << 20);
+/// let mut contents = String::new();
+/// reader.read_to_string(&mut contents)?;
+/// if let Some(trailers) = reader.trailers().map_err(std::io::Error::other)? {
+///     println!("trailers: {:?}", trailers.entries());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BodyReader {
+    // NB: `wait` holds a pollable created from `stream`, and `stream` is a
+    // child of `body`, so they're declared in this order to be dropped first.
+    wait: Option<WaitFor>,
+    stream: InputStream,
+    body: IncomingBody,
+    limit: Option<u64>,
+    received: u64,
+}
+
+impl BodyReader {
+    /// Creates a reader over the contents of `body`.
+    ///
+    /// Returns an error if the body's stream has already been taken with
+    /// [`IncomingBody::stream`].
+    pub fn new(body: IncomingBody) -> io::Result<BodyReader> {
+        let stream = body
+            .stream()
+            .map_err(|()| io::Error::other("body stream has already been taken"))?;
+        Ok(BodyReader {
+            wait: None,
+            stream,
+            body,
+            limit: None,
+            received: 0,
+        })
+    }
+
+    /// Limits the contents to at most `limit` bytes.
+    ///
+    /// Reads fail with [`io::ErrorKind::FileTooLarge`] once the body turns out
+    /// to be longer, so that `read_to_end` and `read_to_string` can be used on
+    /// untrusted bodies without reading all of them into memory.
+    pub fn limit(mut self, limit: u64) -> BodyReader {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns a reference to the underlying body.
+    pub fn body(&self) -> &IncomingBody {
+        &self.body
+    }
+
+    /// Attempts to read bytes into `buf`, returning how many were read.
+    ///
+    /// Returns `Ok(0)` at the end of the body.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            if let Some(wait) = &mut self.wait {
+                ready!(Pin::new(wait).poll(cx));
+                self.wait = None;
+            }
+            let len = request_len(self.limit, self.received, buf);
+            match self.stream.read(len) {
+                Ok(chunk) if chunk.is_empty() => {
+                    self.wait = Some(runtime::wait_for(self.stream.subscribe()));
+                }
+                result => return Poll::Ready(self.receive(result, buf)),
+            }
+        }
+    }
+
+    /// Reads bytes into `buf`, returning how many were read.
+    ///
+    /// Returns `Ok(0)` at the end of the body.
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Finishes the body and waits for its trailers, returning `None` if
+    /// there are none.
+    ///
+    /// Any contents which haven't been read yet are discarded.
+    pub fn trailers(self) -> Result<Option<Trailers>, ErrorCode> {
+        let future = self.finish();
+        loop {
+            match trailers(&future) {
+                Some(result) => return result,
+                None => future.subscribe().block(),
+            }
+        }
+    }
+
+    /// Finishes the body and asynchronously waits for its trailers, returning
+    /// `None` if there are none.
+    ///
+    /// Any contents which haven't been read yet are discarded.
+    pub async fn trailers_async(self) -> Result<Option<Trailers>, ErrorCode> {
+        let future = self.finish();
+        loop {
+            match trailers(&future) {
+                Some(result) => return result,
+                None => runtime::wait_for(future.subscribe()).await,
+            }
+        }
+    }
+
+    fn finish(self) -> FutureTrailers {
+        let BodyReader {
+            wait, stream, body, ..
+        } = self;
+        drop(wait);
+        drop(stream);
+        IncomingBody::finish(body)
+    }
+
+    /// Copies a chunk read from the stream into `buf` and counts it against
+    /// the limit.
+    fn receive(
+        &mut self,
+        result: Result<Vec<u8>, StreamError>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let chunk = match result {
+            Ok(chunk) => chunk,
+            Err(StreamError::Closed) => return Ok(0),
+            Err(e) => return Err(stream_error(e)),
+        };
+        let n = copy_chunk(&chunk, buf)?;
+        self.received = received(self.limit, self.received, n)?;
+        Ok(n)
+    }
+}
+
+/// Returns how many bytes to read into `buf` once `received` bytes have
+/// been, which is one more than `limit` allows so that exceeding it is
+/// noticed.
+fn request_len(limit: Option<u64>, received: u64, buf: &[u8]) -> u64 {
+    let len = u64::try_from(buf.len()).unwrap_or(u64::MAX);
+    match limit {
+        Some(limit) => len.min(limit.saturating_sub(received).saturating_add(1)),
+        None => len,
+    }
+}
+
+/// Returns how many bytes have been received once `n` more were, failing
+/// with [`io::ErrorKind::FileTooLarge`] if that's more than `limit`.
+fn received(limit: Option<u64>, received: u64, n: usize) -> io::Result<u64> {
+    let received = received.saturating_add(n as u64);
+    match limit {
+        Some(limit) if received > limit => Err(io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("body is longer than the limit of {limit} bytes"),
+        )),
+        _ => Ok(received),
+    }
+}
+
+impl io::Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = request_len(self.limit, self.received, buf);
+        let result = self.stream.blocking_read(len);
+        self.receive(result, buf)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for BodyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for BodyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = ready!(self.get_mut().poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "json")]
+impl BodyReader {
+    /// Reads the rest of the contents and deserializes them as JSON.
+    ///
+    /// The contents are subject to the [`limit`](BodyReader::limit), if one
+    /// was set. The reader isn't consumed, so the body can still be finished
+    /// with [`trailers`](BodyReader::trailers) afterwards.
+    ///
+    /// ```no_run
+    /// use wasi::http::body::BodyReader;
+    /// use wasi::http::response::Response;
+    /// use wasi::http::types::{IncomingRequest, ResponseOutparam};
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Greeting {
+    ///     name: String,
+    /// }
+    ///
+    /// fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+    ///     let body = request.consume().unwrap();
+    ///     let mut reader = BodyReader::new(body).unwrap().limit(64 << 10);
+    ///     let response = match reader.json::<Greeting>() {
+    ///         Ok(greeting) => Response::text(format!("hello, {}", greeting.name)),
+    ///         // Malformed or oversized bodies are answered with a 4xx response.
+    ///         Err(e) => e.to_response(),
+    ///     };
+    ///     let _ = response.send(response_out);
+    /// }
+    /// ```
+    pub fn json<T: serde::de::DeserializeOwned>(&mut self) -> Result<T, JsonError> {
+        let mut contents = Vec::new();
+        io::Read::read_to_end(self, &mut contents).map_err(JsonError::Read)?;
+        serde_json::from_slice(&contents).map_err(JsonError::Invalid)
+    }
+}
+
+/// Errors which can happen when reading a JSON body with
+/// [`BodyReader::json`].
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum JsonError {
+    /// Reading the contents failed, or they were longer than the limit.
+    Read(io::Error),
+    /// The contents were not valid JSON for the expected type.
+    Invalid(serde_json::Error),
+}
+
+#[cfg(feature = "json")]
+impl JsonError {
+    /// Returns the status code to respond to a request with this error with:
+    /// `413 Content Too Large` if the body was longer than the limit and
+    /// `400 Bad Request` otherwise.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            JsonError::Read(e) if e.kind() == io::ErrorKind::FileTooLarge => 413,
+            JsonError::Read(_) | JsonError::Invalid(_) => 400,
+        }
+    }
+
+    /// Creates a response to a request with this error, with the
+    /// [`status`](JsonError::status) and the error message as its body.
+    pub fn to_response(&self) -> Response {
+        Response::builder()
+            .status(self.status())
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(self.to_string())
+    }
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Read(e) => write!(f, "failed to read body: {e}"),
+            JsonError::Invalid(e) => write!(f, "invalid JSON body: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonError::Read(e) => Some(e),
+            JsonError::Invalid(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<JsonError> for io::Error {
+    fn from(e: JsonError) -> io::Error {
+        match e {
+            JsonError::Read(e) => e,
+            JsonError::Invalid(e) => e.into(),
+        }
+    }
+}
+
+/// Returns the trailers of `future` if they've been received.
+fn trailers(future: &FutureTrailers) -> Option<Result<Option<Trailers>, ErrorCode>> {
+    future
+        .get()
+        .map(|result| result.expect("trailers are only taken once"))
+}
+
+/// A writer of the contents of an [`OutgoingBody`], which finishes the body
+/// correctly.
+///
+/// The body's [`OutputStream`] is a child resource which must be dropped
+/// before the body is finished with [`OutgoingBody::finish`], and a body
+/// which is never finished is treated by the host as if sending it failed.
+/// This type takes care of both: [`finish`](BodyWriter::finish) flushes and
+/// drops the stream before finishing the body, and dropping the writer
+/// finishes the body the same way, without trailers. Writers are dropped
+/// without finishing the body if a write failed or the thread is panicking,
+/// so that a partially written body isn't mistaken for a complete one.
+///
+/// Writers created with [`for_request`](BodyWriter::for_request) or
+/// [`for_response`](BodyWriter::for_response) also check the contents against
+/// the `content-length` header. Writing more than that fails right away, and
+/// finishing with less fails with [`ErrorCode::HttpRequestBodySize`] or
+/// [`ErrorCode::HttpResponseBodySize`] before the body is handed to the host.
+///
+/// ```no_run
+/// use std::io::Write;
+/// use wasi::http::body::BodyWriter;
+/// use wasi::http::types::{Fields, OutgoingResponse, ResponseOutparam};
+///
+/// # fn handle(response_out: ResponseOutparam) -> std::io::Result<()> {
+/// let response = OutgoingResponse::new(Fields::new());
+/// let mut body = BodyWriter::for_response(&response)?;
+/// ResponseOutparam::set(response_out, Ok(response));
+/// body.write_all(b"hello, world")?;
+/// body.finish(None).map_err(std::io::Error::other)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct BodyWriter {
+    // NB: `stream` is declared before `body` so it is dropped first. Both are
+    // taken when the body is finished.
+    stream: Option<OutputStream>,
+    body: Option<OutgoingBody>,
+    content_length: Option<u64>,
+    written: u64,
+    size_error: fn(Option<u64>) -> ErrorCode,
+    failed: bool,
+}
+
+impl BodyWriter {
+    /// Creates a writer of the contents of `body`, without checking their
+    /// length.
+    ///
+    /// Returns an error if the body's stream has already been taken with
+    /// [`OutgoingBody::write`].
+    pub fn new(body: OutgoingBody) -> io::Result<BodyWriter> {
+        BodyWriter::with_size_error(body, None, ErrorCode::HttpResponseBodySize)
+    }
+
+    /// Creates a writer of the body of `request`, which is checked against
+    /// its `content-length` header.
+    pub fn for_request(request: &OutgoingRequest) -> io::Result<BodyWriter> {
+        let body = request
+            .body()
+            .map_err(|()| io::Error::other("request body has already been taken"))?;
+        let content_length = content_length(&request.headers())?;
+        BodyWriter::with_size_error(body, content_length, ErrorCode::HttpRequestBodySize)
+    }
+
+    /// Creates a writer of the body of `response`, which is checked against
+    /// its `content-length` header.
+    pub fn for_response(response: &OutgoingResponse) -> io::Result<BodyWriter> {
+        let body = response
+            .body()
+            .map_err(|()| io::Error::other("response body has already been taken"))?;
+        let content_length = content_length(&response.headers())?;
+        BodyWriter::with_size_error(body, content_length, ErrorCode::HttpResponseBodySize)
+    }
+
+    fn with_size_error(
+        body: OutgoingBody,
+        content_length: Option<u64>,
+        size_error: fn(Option<u64>) -> ErrorCode,
+    ) -> io::Result<BodyWriter> {
+        let stream = body
+            .write()
+            .map_err(|()| io::Error::other("body stream has already been taken"))?;
+        Ok(BodyWriter {
+            stream: Some(stream),
+            body: Some(body),
+            content_length,
+            written: 0,
+            size_error,
+            failed: false,
+        })
+    }
+
+    /// Returns how many bytes have been written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Flushes the contents and finishes the body with `trailers`.
+    ///
+    /// Fails if flushing fails, if fewer bytes were written than the
+    /// `content-length` header promised, or if the host rejects the body or
+    /// its trailers. On failure the body is dropped without being finished,
+    /// so the host treats it as failed too.
+    pub fn finish(mut self, trailers: Option<Trailers>) -> Result<(), ErrorCode> {
+        self.finish_inner(trailers)
+    }
+
+    fn finish_inner(&mut self, trailers: Option<Trailers>) -> Result<(), ErrorCode> {
+        let (Some(stream), Some(body)) = (self.stream.take(), self.body.take()) else {
+            return Ok(());
+        };
+        let flushed = stream.blocking_flush();
+        drop(stream);
+        if let Err(e) = flushed {
+            return Err(match e {
+                StreamError::LastOperationFailed(e) => http_error_code(&e)
+                    .unwrap_or_else(|| ErrorCode::InternalError(Some(e.to_debug_string()))),
+                StreamError::Closed => ErrorCode::InternalError(Some("body stream closed".into())),
+            });
+        }
+        if !is_complete(self.content_length, self.written) {
+            return Err((self.size_error)(Some(self.written)));
+        }
+        OutgoingBody::finish(body, trailers)
+    }
+
+    fn write_inner(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let stream = self
+            .stream
+            .as_ref()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(total) = overrun(self.content_length, self.written, buf) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                (self.size_error)(Some(total)),
+            ));
+        }
+        let permit = loop {
+            match stream.check_write() {
+                Ok(0) => stream.subscribe().block(),
+                Ok(permit) => break permit,
+                Err(e) => return Err(stream_error(e)),
+            }
+        };
+        let n = write_len(permit, buf);
+        stream.write(&buf[..n]).map_err(stream_error)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+}
+
+impl io::Write for BodyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.write_inner(buf);
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match &self.stream {
+            Some(stream) => stream.blocking_flush().map_err(stream_error),
+            None => Ok(()),
+        };
+        self.failed |= result.is_err();
+        result
+    }
+}
+
+impl Drop for BodyWriter {
+    fn drop(&mut self) {
+        // Dropping the body without finishing it tells the host that sending
+        // it failed.
+        if self.failed || std::thread::panicking() {
+            return;
+        }
+        // There's nobody to report an error to at this point, and a body
+        // which failed to finish is reported to the host as failed anyway.
+        let _ = self.finish_inner(None);
+    }
+}
+
+/// Returns the total length of the contents if writing `buf` once `written`
+/// bytes have been would exceed `content_length`.
+fn overrun(content_length: Option<u64>, written: u64, buf: &[u8]) -> Option<u64> {
+    let total = written.saturating_add(buf.len() as u64);
+    content_length.filter(|&len| total > len).map(|_| total)
+}
+
+/// Returns whether `written` bytes are as many as `content_length` promised,
+/// if there was one.
+fn is_complete(content_length: Option<u64>, written: u64) -> bool {
+    match content_length {
+        Some(len) => written == len,
+        None => true,
+    }
+}
+
+/// Returns the value of the `content-length` header of `headers`.
+fn content_length(headers: &Fields) -> io::Result<Option<u64>> {
+    let Some(value) = headers.get(&"content-length".into()).into_iter().next() else {
+        return Ok(None);
+    };
+    core::str::from_utf8(&value)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .map(Some)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid content-length header"))
+}
+
+/// Converts a [`StreamError`] of a body's stream into an [`io::Error`],
+/// recovering the HTTP error code behind it, which
+/// `From<StreamError> for io::Error` doesn't look up.
+///
+/// This is used by [`BodyReader`] and [`BodyWriter`], and is useful for
+/// streams taken directly from an [`IncomingBody`] or [`OutgoingBody`]. The
+/// [`ErrorCode`] can be recovered from the returned error with
+/// [`io::Error::get_ref`] and `downcast_ref`.
+pub fn stream_error(err: StreamError) -> io::Error {
+    match err {
+        StreamError::Closed => io::Error::from(io::ErrorKind::BrokenPipe),
+        StreamError::LastOperationFailed(e) => match http_error_code(&e) {
+            Some(code) => io::Error::other(code),
+            None => io::Error::other(e.to_debug_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_lens() {
+        let buf = [0; 16];
+        assert_eq!(request_len(None, 0, &buf), 16);
+        assert_eq!(request_len(None, u64::MAX, &buf), 16);
+        assert_eq!(request_len(Some(100), 0, &buf), 16);
+        // One byte past the limit is requested to notice exceeding it.
+        assert_eq!(request_len(Some(10), 0, &buf), 11);
+        assert_eq!(request_len(Some(10), 8, &buf), 3);
+        assert_eq!(request_len(Some(10), 10, &buf), 1);
+        assert_eq!(request_len(Some(0), 0, &buf), 1);
+        assert_eq!(request_len(Some(u64::MAX), 0, &buf), 16);
+        assert_eq!(request_len(Some(10), 0, &[]), 0);
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(received(None, 0, 16).unwrap(), 16);
+        assert_eq!(received(None, u64::MAX, 16).unwrap(), u64::MAX);
+        assert_eq!(received(Some(10), 0, 10).unwrap(), 10);
+        assert_eq!(received(Some(10), 8, 2).unwrap(), 10);
+        assert_eq!(received(Some(10), 10, 0).unwrap(), 10);
+        for (limit, received_before, n) in [(10, 0, 11), (10, 10, 1), (0, 0, 1)] {
+            let err = received(Some(limit), received_before, n).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::FileTooLarge);
+        }
+    }
+
+    #[test]
+    fn content_lengths() {
+        assert_eq!(overrun(None, 0, b"abc"), None);
+        assert_eq!(overrun(None, u64::MAX, b"abc"), None);
+        assert_eq!(overrun(Some(3), 0, b"abc"), None);
+        assert_eq!(overrun(Some(5), 2, b"abc"), None);
+        assert_eq!(overrun(Some(5), 5, b""), None);
+        assert_eq!(overrun(Some(2), 0, b"abc"), Some(3));
+        assert_eq!(overrun(Some(5), 4, b"abc"), Some(7));
+        assert_eq!(overrun(Some(0), 0, b"a"), Some(1));
+
+        assert!(is_complete(None, 0));
+        assert!(is_complete(None, 7));
+        assert!(is_complete(Some(0), 0));
+        assert!(is_complete(Some(7), 7));
+        assert!(!is_complete(Some(7), 6));
+        assert!(!is_complete(Some(7), 0));
+    }
+}
@@ -0,0 +1,513 @@
+//! A blocking HTTP client built on [`outgoing_handler::handle`].
+//!
+//! This wraps the `wasi:http/outgoing-handler` interface, taking care of
+//! building the [`OutgoingRequest`], writing and finishing its body, waiting
+//! on the [`FutureIncomingResponse`], and unpacking the result.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use wasi::http::client::Client;
+//!
+//! # fn main() -> Result<(), wasi::http::client::Error> {
+//! // One-off requests can use the free functions in this module.
+//! let body = wasi::http::client::get("https://example.com/")?.text()?;
+//!
+//! // Otherwise a `Client` holds settings shared by many requests.
+//! let client = Client::new().connect_timeout(Duration::from_secs(5));
+//! let response = client
+//!     .post("https://example.com/api")
+//!     .header("content-type", "text/plain")
+//!     .body("hello")
+//!     .send()?;
+//! assert_eq!(response.status(), 200);
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "json")]
+use crate::http::body::JsonError;
+use crate::http::body::{BodyReader, BodyWriter};
+use crate::http::outgoing_handler;
+use crate::http::types::{
+    ErrorCode, FieldKey, FieldValue, Fields, FutureIncomingResponse, HeaderError, IncomingResponse,
+    Method, OutgoingRequest, RequestOptions, Scheme, StatusCode,
+};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::string::String;
+use std::time::Duration;
+use std::vec::Vec;
+
+/// Sends a `GET` request to `url` with a default [`Client`].
+pub fn get(url: &str) -> Result<Response, Error> {
+    Client::new().get(url).send()
+}
+
+/// Settings shared by every request sent through it, such as timeouts.
+#[derive(Clone, Debug, Default)]
+pub struct Client {
+    timeouts: Timeouts,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Timeouts {
+    connect: Option<Duration>,
+    first_byte: Option<Duration>,
+    between_bytes: Option<Duration>,
+}
+
+impl Client {
+    /// Creates a new client which doesn't configure any timeouts, leaving
+    /// them up to the host.
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    /// Sets the timeout for the initial connect to the HTTP server.
+    ///
+    /// This corresponds to [`RequestOptions::set_connect_timeout`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Client {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for receiving the first byte of the response.
+    ///
+    /// This corresponds to [`RequestOptions::set_first_byte_timeout`].
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> Client {
+        self.timeouts.first_byte = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout between receiving subsequent chunks of the response.
+    ///
+    /// This corresponds to [`RequestOptions::set_between_bytes_timeout`].
+    pub fn between_bytes_timeout(mut self, timeout: Duration) -> Client {
+        self.timeouts.between_bytes = Some(timeout);
+        self
+    }
+
+    /// Starts building a request with the given `method` to `url`.
+    pub fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        RequestBuilder {
+            method,
+            url: Url::parse(url),
+            headers: Vec::new(),
+            body: Vec::new(),
+            timeouts: self.timeouts,
+            error: None,
+        }
+    }
+
+    /// Starts building a `GET` request to `url`.
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Get, url)
+    }
+
+    /// Starts building a `HEAD` request to `url`.
+    pub fn head(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Head, url)
+    }
+
+    /// Starts building a `POST` request to `url`.
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Post, url)
+    }
+
+    /// Starts building a `PUT` request to `url`.
+    pub fn put(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Put, url)
+    }
+
+    /// Starts building a `PATCH` request to `url`.
+    pub fn patch(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Patch, url)
+    }
+
+    /// Starts building a `DELETE` request to `url`.
+    pub fn delete(&self, url: &str) -> RequestBuilder {
+        self.request(Method::Delete, url)
+    }
+}
+
+/// A request which is being built, created through a [`Client`].
+pub struct RequestBuilder {
+    method: Method,
+    url: Result<Url, Error>,
+    headers: Vec<(FieldKey, FieldValue)>,
+    body: Vec<u8>,
+    timeouts: Timeouts,
+    // The first error of a builder method, deferred until `send`.
+    error: Option<Error>,
+}
+
+impl RequestBuilder {
+    /// Appends a header to the request.
+    ///
+    /// Invalid or forbidden headers are reported when the request is sent.
+    pub fn header(mut self, name: &str, value: impl Into<Vec<u8>>) -> RequestBuilder {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets the body of the request.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> RequestBuilder {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets the body of the request to `value` serialized as JSON, along with
+    /// a `content-type: application/json` header.
+    ///
+    /// Values which can't be serialized are reported when the request is sent.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, value: &T) -> RequestBuilder {
+        match serde_json::to_vec(value) {
+            Ok(body) => self.header("content-type", "application/json").body(body),
+            Err(e) => {
+                self.error.get_or_insert(Error::Json(e));
+                self
+            }
+        }
+    }
+
+    /// Sets the timeout for the initial connect to the HTTP server, overriding
+    /// the client's setting.
+    pub fn connect_timeout(mut self, timeout: Duration) -> RequestBuilder {
+        self.timeouts.connect = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for receiving the first byte of the response,
+    /// overriding the client's setting.
+    pub fn first_byte_timeout(mut self, timeout: Duration) -> RequestBuilder {
+        self.timeouts.first_byte = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout between receiving subsequent chunks of the response,
+    /// overriding the client's setting.
+    pub fn between_bytes_timeout(mut self, timeout: Duration) -> RequestBuilder {
+        self.timeouts.between_bytes = Some(timeout);
+        self
+    }
+
+    /// Sends the request, blocking until the response's status and headers
+    /// have been received.
+    pub fn send(self) -> Result<Response, Error> {
+        let url = self.url?;
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let headers = Fields::from_list(&self.headers).map_err(Error::Header)?;
+        let request = OutgoingRequest::new(headers);
+        request
+            .set_method(&self.method)
+            .map_err(|()| Error::Request("invalid method"))?;
+        request
+            .set_scheme(Some(&url.scheme))
+            .map_err(|()| Error::Request("invalid scheme"))?;
+        request
+            .set_authority(Some(&url.authority))
+            .map_err(|()| Error::Request("invalid authority"))?;
+        request
+            .set_path_with_query(Some(&url.path_with_query))
+            .map_err(|()| Error::Request("invalid path"))?;
+        let options = self.timeouts.to_options()?;
+
+        let mut body = BodyWriter::for_request(&request).map_err(Error::Io)?;
+        let future = outgoing_handler::handle(request, options).map_err(Error::Http)?;
+        body.write_all(&self.body).map_err(Error::Io)?;
+        body.finish(None).map_err(Error::Http)?;
+
+        Response::wait(future)
+    }
+}
+
+impl Timeouts {
+    fn to_options(self) -> Result<Option<RequestOptions>, Error> {
+        if self.connect.is_none() && self.first_byte.is_none() && self.between_bytes.is_none() {
+            return Ok(None);
+        }
+        let options = RequestOptions::new();
+        if let Some(timeout) = self.connect {
+            options
+                .set_connect_timeout(Some(nanos(timeout)))
+                .map_err(|()| Error::Request("connect timeout is not supported"))?;
+        }
+        if let Some(timeout) = self.first_byte {
+            options
+                .set_first_byte_timeout(Some(nanos(timeout)))
+                .map_err(|()| Error::Request("first byte timeout is not supported"))?;
+        }
+        if let Some(timeout) = self.between_bytes {
+            options
+                .set_between_bytes_timeout(Some(nanos(timeout)))
+                .map_err(|()| Error::Request("between bytes timeout is not supported"))?;
+        }
+        Ok(Some(options))
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// The response to a request sent with a [`Client`].
+pub struct Response {
+    status: StatusCode,
+    headers: Vec<(FieldKey, FieldValue)>,
+    incoming: IncomingResponse,
+}
+
+impl Response {
+    fn wait(future: FutureIncomingResponse) -> Result<Response, Error> {
+        let result = loop {
+            match future.get() {
+                Some(result) => break result,
+                None => future.subscribe().block(),
+            }
+        };
+        let incoming = result
+            .expect("response is only taken once")
+            .map_err(Error::Http)?;
+        let headers = incoming.headers().entries();
+        Ok(Response {
+            status: incoming.status(),
+            headers,
+            incoming,
+        })
+    }
+
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns every header of the response, in the order they were received.
+    pub fn headers(&self) -> &[(FieldKey, FieldValue)] {
+        &self.headers
+    }
+
+    /// Returns the value of the first header named `name`, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Returns a reader which streams the body of the response.
+    pub fn into_body(self) -> Result<BodyReader, Error> {
+        let body = self
+            .incoming
+            .consume()
+            .expect("body of a new response is available");
+        BodyReader::new(body).map_err(Error::Io)
+    }
+
+    /// Reads the entire body of the response.
+    pub fn bytes(self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.into_body()?
+            .read_to_end(&mut bytes)
+            .map_err(Error::Io)?;
+        Ok(bytes)
+    }
+
+    /// Reads the entire body of the response and deserializes it as JSON.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::de::DeserializeOwned>(self) -> Result<T, Error> {
+        self.into_body()?.json().map_err(|e| match e {
+            JsonError::Read(e) => Error::Io(e),
+            JsonError::Invalid(e) => Error::Json(e),
+        })
+    }
+
+    /// Reads the entire body of the response as UTF-8 text.
+    pub fn text(self) -> Result<String, Error> {
+        let mut text = String::new();
+        self.into_body()?
+            .read_to_string(&mut text)
+            .map_err(Error::Io)?;
+        Ok(text)
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Errors which can happen when sending a request with a [`Client`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The URL of the request could not be parsed.
+    InvalidUrl(String),
+    /// A header of the request was invalid or forbidden.
+    Header(HeaderError),
+    /// The host rejected part of the request, such as its method or one of
+    /// its timeouts.
+    Request(&'static str),
+    /// The request failed with a protocol error.
+    Http(ErrorCode),
+    /// Writing the request body or reading the response body failed.
+    Io(io::Error),
+    /// The request body could not be serialized as JSON, or the response
+    /// body was not valid JSON for the expected type.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidUrl(url) => write!(f, "invalid URL: {url}"),
+            Error::Header(e) => write!(f, "invalid header: {e}"),
+            Error::Request(msg) => f.write_str(msg),
+            Error::Http(e) => write!(f, "HTTP error: {e}"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            #[cfg(feature = "json")]
+            Error::Json(e) => write!(f, "JSON error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Header(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Io(e) => Some(e),
+            #[cfg(feature = "json")]
+            Error::Json(e) => Some(e),
+            Error::InvalidUrl(_) | Error::Request(_) => None,
+        }
+    }
+}
+
+/// The parts of an absolute URL which are set on an [`OutgoingRequest`].
+struct Url {
+    scheme: Scheme,
+    authority: String,
+    path_with_query: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Url, Error> {
+        let invalid = || Error::InvalidUrl(url.into());
+        let (scheme, rest) = url.split_once("://").ok_or_else(invalid)?;
+        let scheme = match scheme {
+            s if s.eq_ignore_ascii_case("http") => Scheme::Http,
+            s if s.eq_ignore_ascii_case("https") => Scheme::Https,
+            "" => return Err(invalid()),
+            s => Scheme::Other(s.into()),
+        };
+        // Fragments are never sent to the server.
+        let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+        let (authority, path_with_query) = match rest.find(['/', '?']) {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        if authority.is_empty() {
+            return Err(invalid());
+        }
+        let path_with_query = match path_with_query {
+            "" => "/".into(),
+            p if p.starts_with('?') => ["/", p].concat(),
+            p => p.into(),
+        };
+        Ok(Url {
+            scheme,
+            authority: authority.into(),
+            path_with_query,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> (Scheme, String, String) {
+        let url = Url::parse(url).unwrap();
+        (url.scheme, url.authority, url.path_with_query)
+    }
+
+    #[test]
+    fn default_ports() {
+        let (scheme, authority, path) = parse("http://example.com/");
+        assert!(matches!(scheme, Scheme::Http));
+        assert_eq!(authority, "example.com");
+        assert_eq!(path, "/");
+
+        let (scheme, authority, _) = parse("HTTPS://example.com");
+        assert!(matches!(scheme, Scheme::Https));
+        assert_eq!(authority, "example.com");
+
+        // Explicit ports, default or not, are passed through to the host.
+        assert_eq!(parse("http://example.com:80/").1, "example.com:80");
+        assert_eq!(parse("https://example.com:8443/a").1, "example.com:8443");
+    }
+
+    #[test]
+    fn other_schemes() {
+        let (scheme, authority, _) = parse("ws://example.com/");
+        assert!(matches!(scheme, Scheme::Other(s) if s == "ws"));
+        assert_eq!(authority, "example.com");
+    }
+
+    #[test]
+    fn ipv6_hosts() {
+        let (_, authority, path) = parse("http://[::1]:8080/x?y");
+        assert_eq!(authority, "[::1]:8080");
+        assert_eq!(path, "/x?y");
+
+        let (_, authority, path) = parse("http://[fe80::1%25eth0]");
+        assert_eq!(authority, "[fe80::1%25eth0]");
+        assert_eq!(path, "/");
+    }
+
+    #[test]
+    fn paths_and_queries() {
+        assert_eq!(parse("http://example.com").2, "/");
+        assert_eq!(parse("http://example.com?q=1").2, "/?q=1");
+        assert_eq!(parse("http://example.com/a/b?q=1").2, "/a/b?q=1");
+        assert_eq!(parse("http://example.com/a#frag").2, "/a");
+        assert_eq!(parse("http://example.com#frag").2, "/");
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for url in ["", "example.com", "://example.com", "http://", "http:///a"] {
+            assert!(
+                matches!(Url::parse(url), Err(Error::InvalidUrl(u)) if u == url),
+                "{url}"
+            );
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_bodies() {
+        let request = Client::new().post("http://example.com/").json(&[("a", 1)]);
+        assert!(request.error.is_none());
+        assert_eq!(request.body, b"[[\"a\",1]]");
+        assert_eq!(
+            request.headers,
+            [("content-type".into(), b"application/json".to_vec())]
+        );
+
+        // Maps with keys other than strings can't be serialized.
+        let map = std::collections::BTreeMap::from([((1, 2), 3)]);
+        let request = Client::new().post("http://example.com/").json(&map);
+        assert!(matches!(request.error, Some(Error::Json(_))));
+        assert!(request.body.is_empty() && request.headers.is_empty());
+    }
+}
@@ -0,0 +1,484 @@
+//! Conversions between the types in [`wasi::http::types`](crate::http::types)
+//! and the [`http`](::http) crate.
+//!
+//! This module is only available with the `http` feature enabled.
+//!
+//! The status code, headers, method and URI of `http` requests and responses
+//! can be converted into an [`OutgoingRequest`] or [`OutgoingResponse`] from
+//! their `Parts`.
+//!
+//! Status codes are represented as a plain `u16` in `wasi:http` and can be
+//! converted with [`http::StatusCode::from_u16`](::http::StatusCode::from_u16)
+//! and [`http::StatusCode::as_u16`](::http::StatusCode::as_u16).
+//!
+//! ```no_run
+//! use wasi::http::compat;
+//! use wasi::http::types::{IncomingRequest, ResponseOutparam};
+//!
+//! struct MyIncomingHandler;
+//!
+//! impl wasi::exports::http::incoming_handler::Guest for MyIncomingHandler {
+//!     fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+//!         let request = compat::to_http_request(request).unwrap();
+//!         let response = http::Response::builder()
+//!             .header("content-type", "text/plain")
+//!             .body(format!("Hello from {}!", request.uri().path()))
+//!             .unwrap();
+//!         compat::send_http_response(response_out, response).unwrap();
+//!     }
+//! }
+//!
+//! wasi::http::proxy::export!(MyIncomingHandler);
+//! ```
+
+use crate::http::body::{BodyReader, BodyWriter};
+use crate::http::types::{
+    ErrorCode, FieldKey, FieldValue, Fields, HeaderError, IncomingRequest, Method, OutgoingRequest,
+    OutgoingResponse, ResponseOutparam, Scheme,
+};
+use std::fmt;
+use std::io::{self, Write};
+use std::string::ToString;
+use std::vec::Vec;
+
+impl From<::http::Method> for Method {
+    fn from(method: ::http::Method) -> Method {
+        Method::from(&method)
+    }
+}
+
+impl From<&::http::Method> for Method {
+    fn from(method: &::http::Method) -> Method {
+        match *method {
+            ::http::Method::GET => Method::Get,
+            ::http::Method::HEAD => Method::Head,
+            ::http::Method::POST => Method::Post,
+            ::http::Method::PUT => Method::Put,
+            ::http::Method::DELETE => Method::Delete,
+            ::http::Method::CONNECT => Method::Connect,
+            ::http::Method::OPTIONS => Method::Options,
+            ::http::Method::TRACE => Method::Trace,
+            ::http::Method::PATCH => Method::Patch,
+            _ => Method::Other(method.as_str().into()),
+        }
+    }
+}
+
+impl TryFrom<Method> for ::http::Method {
+    type Error = ::http::method::InvalidMethod;
+
+    fn try_from(method: Method) -> Result<::http::Method, Self::Error> {
+        ::http::Method::try_from(&method)
+    }
+}
+
+impl TryFrom<&Method> for ::http::Method {
+    type Error = ::http::method::InvalidMethod;
+
+    fn try_from(method: &Method) -> Result<::http::Method, Self::Error> {
+        Ok(match method {
+            Method::Get => ::http::Method::GET,
+            Method::Head => ::http::Method::HEAD,
+            Method::Post => ::http::Method::POST,
+            Method::Put => ::http::Method::PUT,
+            Method::Delete => ::http::Method::DELETE,
+            Method::Connect => ::http::Method::CONNECT,
+            Method::Options => ::http::Method::OPTIONS,
+            Method::Trace => ::http::Method::TRACE,
+            Method::Patch => ::http::Method::PATCH,
+            Method::Other(other) => ::http::Method::from_bytes(other.as_bytes())?,
+        })
+    }
+}
+
+impl From<::http::uri::Scheme> for Scheme {
+    fn from(scheme: ::http::uri::Scheme) -> Scheme {
+        Scheme::from(&scheme)
+    }
+}
+
+impl From<&::http::uri::Scheme> for Scheme {
+    fn from(scheme: &::http::uri::Scheme) -> Scheme {
+        // Schemes are case-insensitive, but `http` only normalizes the case
+        // of schemes it parses as part of a URI.
+        match scheme.as_str() {
+            s if s.eq_ignore_ascii_case("http") => Scheme::Http,
+            s if s.eq_ignore_ascii_case("https") => Scheme::Https,
+            s => Scheme::Other(s.into()),
+        }
+    }
+}
+
+impl TryFrom<Scheme> for ::http::uri::Scheme {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(scheme: Scheme) -> Result<::http::uri::Scheme, Self::Error> {
+        ::http::uri::Scheme::try_from(&scheme)
+    }
+}
+
+impl TryFrom<&Scheme> for ::http::uri::Scheme {
+    type Error = ::http::uri::InvalidUri;
+
+    fn try_from(scheme: &Scheme) -> Result<::http::uri::Scheme, Self::Error> {
+        Ok(match scheme {
+            Scheme::Http => ::http::uri::Scheme::HTTP,
+            Scheme::Https => ::http::uri::Scheme::HTTPS,
+            Scheme::Other(other) => other.parse()?,
+        })
+    }
+}
+
+impl TryFrom<&::http::HeaderMap> for Fields {
+    type Error = HeaderError;
+
+    /// Creates a new, mutable, [`Fields`] with every entry of `headers`.
+    ///
+    /// Fails with [`HeaderError::Forbidden`] if the host doesn't allow one of
+    /// the headers to be set.
+    fn try_from(headers: &::http::HeaderMap) -> Result<Fields, HeaderError> {
+        Fields::from_list(&to_entries(headers))
+    }
+}
+
+impl TryFrom<&Fields> for ::http::HeaderMap {
+    type Error = HeaderError;
+
+    /// Copies every entry of `fields` into a new [`HeaderMap`](::http::HeaderMap).
+    ///
+    /// Fails with [`HeaderError::InvalidSyntax`] if a name or value isn't
+    /// accepted by the `http` crate.
+    fn try_from(fields: &Fields) -> Result<::http::HeaderMap, HeaderError> {
+        from_entries(fields.entries())
+    }
+}
+
+/// Returns the entries of `headers` in the form of [`Fields::entries`],
+/// keeping every value of repeated headers in order.
+fn to_entries(headers: &::http::HeaderMap) -> Vec<(FieldKey, FieldValue)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().into(), value.as_bytes().to_vec()))
+        .collect()
+}
+
+/// Collects entries in the form of [`Fields::entries`] into a
+/// [`HeaderMap`](::http::HeaderMap).
+fn from_entries(
+    entries: impl IntoIterator<Item = (FieldKey, FieldValue)>,
+) -> Result<::http::HeaderMap, HeaderError> {
+    let mut headers = ::http::HeaderMap::new();
+    for (name, value) in entries {
+        let name = ::http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| HeaderError::InvalidSyntax)?;
+        let value =
+            ::http::HeaderValue::from_bytes(&value).map_err(|_| HeaderError::InvalidSyntax)?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+impl TryFrom<&::http::response::Parts> for OutgoingResponse {
+    type Error = Error;
+
+    /// Creates a response with the status code and headers of `parts`.
+    fn try_from(parts: &::http::response::Parts) -> Result<OutgoingResponse, Error> {
+        let headers = Fields::try_from(&parts.headers).map_err(Error::Header)?;
+        let response = OutgoingResponse::new(headers);
+        response
+            .set_status_code(parts.status.as_u16())
+            .map_err(|()| Error::Status(parts.status.as_u16()))?;
+        Ok(response)
+    }
+}
+
+impl TryFrom<&::http::request::Parts> for OutgoingRequest {
+    type Error = Error;
+
+    /// Creates a request with the method, URI and headers of `parts`.
+    fn try_from(parts: &::http::request::Parts) -> Result<OutgoingRequest, Error> {
+        let headers = Fields::try_from(&parts.headers).map_err(Error::Header)?;
+        let request = OutgoingRequest::new(headers);
+        let invalid_uri = || Error::Uri(parts.uri.clone());
+        request
+            .set_method(&Method::from(&parts.method))
+            .map_err(|()| Error::Method(parts.method.clone()))?;
+        request
+            .set_scheme(parts.uri.scheme().map(Scheme::from).as_ref())
+            .map_err(|()| invalid_uri())?;
+        request
+            .set_authority(parts.uri.authority().map(|a| a.as_str()))
+            .map_err(|()| invalid_uri())?;
+        request
+            .set_path_with_query(parts.uri.path_and_query().map(|p| p.as_str()))
+            .map_err(|()| invalid_uri())?;
+        Ok(request)
+    }
+}
+
+/// Converts an [`IncomingRequest`] into an [`http::Request`](::http::Request)
+/// whose body streams the contents of the incoming request.
+pub fn to_http_request(request: IncomingRequest) -> Result<::http::Request<BodyReader>, Error> {
+    let mut uri = ::http::Uri::builder();
+    if let Some(scheme) = request.scheme() {
+        uri = uri.scheme(::http::uri::Scheme::try_from(scheme).map_err(::http::Error::from)?);
+    }
+    if let Some(authority) = request.authority() {
+        uri = uri.authority(authority);
+    }
+    let uri = uri.path_and_query(request.path_with_query().unwrap_or_else(|| "/".into()));
+
+    let method = ::http::Method::try_from(request.method()).map_err(::http::Error::from)?;
+    let headers = ::http::HeaderMap::try_from(&request.headers()).map_err(Error::Header)?;
+    let body = request
+        .consume()
+        .map_err(|()| io::Error::other("request body has already been consumed"))?;
+
+    let mut http_request = ::http::Request::builder()
+        .method(method)
+        .uri(uri.build()?)
+        .body(BodyReader::new(body)?)?;
+    *http_request.headers_mut() = headers;
+    Ok(http_request)
+}
+
+/// Sends an [`http::Response`](::http::Response) through `response_out`,
+/// writing and finishing its body.
+///
+/// The body is written with a [`BodyWriter`], so it's checked against the
+/// `content-length` header of `response`. If the status code or headers of
+/// `response` are rejected by the host then `response_out` is set to an
+/// [`ErrorCode::InternalError`] instead.
+pub fn send_http_response<B>(
+    response_out: ResponseOutparam,
+    response: ::http::Response<B>,
+) -> Result<(), Error>
+where
+    B: AsRef<[u8]>,
+{
+    let (parts, body) = response.into_parts();
+    let outgoing = OutgoingResponse::try_from(&parts).and_then(|outgoing| {
+        let writer = BodyWriter::for_response(&outgoing)?;
+        Ok((outgoing, writer))
+    });
+    let mut writer = match outgoing {
+        Ok((outgoing, writer)) => {
+            ResponseOutparam::set(response_out, Ok(outgoing));
+            writer
+        }
+        Err(e) => {
+            ResponseOutparam::set(
+                response_out,
+                Err(ErrorCode::InternalError(Some(e.to_string()))),
+            );
+            return Err(e);
+        }
+    };
+    writer.write_all(body.as_ref())?;
+    writer.finish(None).map_err(Error::Body)
+}
+
+/// Errors converting between `wasi:http` and the `http` crate.
+#[derive(Debug)]
+pub enum Error {
+    /// A header name or value was invalid or forbidden.
+    Header(HeaderError),
+    /// A method, URI or status code was rejected by the `http` crate.
+    Http(::http::Error),
+    /// The host rejected the method of a request.
+    Method(::http::Method),
+    /// The host rejected the URI of a request.
+    Uri(::http::Uri),
+    /// The host rejected the status code of a response.
+    Status(u16),
+    /// Finishing an outgoing body failed, including because it was shorter
+    /// than its `content-length` header.
+    Body(ErrorCode),
+    /// Reading or writing a body failed.
+    Io(io::Error),
+}
+
+impl From<::http::Error> for Error {
+    fn from(e: ::http::Error) -> Error {
+        Error::Http(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Header(e) => write!(f, "invalid header: {e}"),
+            Error::Http(e) => e.fmt(f),
+            Error::Method(method) => write!(f, "method `{method}` is not allowed"),
+            Error::Uri(uri) => write!(f, "URI `{uri}` is not allowed"),
+            Error::Status(status) => write!(f, "status code `{status}` is not allowed"),
+            Error::Body(e) => write!(f, "failed to finish body: {e}"),
+            Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Header(e) => Some(e),
+            Error::Http(e) => Some(e),
+            Error::Body(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Method(_) | Error::Uri(_) | Error::Status(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    fn method_name(method: &Method) -> &str {
+        match method {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Other(other) => other,
+        }
+    }
+
+    #[test]
+    fn standard_methods() {
+        for (http, standard) in [
+            (::http::Method::GET, true),
+            (::http::Method::HEAD, true),
+            (::http::Method::POST, true),
+            (::http::Method::PUT, true),
+            (::http::Method::DELETE, true),
+            (::http::Method::CONNECT, true),
+            (::http::Method::OPTIONS, true),
+            (::http::Method::TRACE, true),
+            (::http::Method::PATCH, true),
+            (::http::Method::from_bytes(b"PURGE").unwrap(), false),
+            (::http::Method::from_bytes(b"get").unwrap(), false),
+        ] {
+            let method = Method::from(&http);
+            assert_eq!(method_name(&method), http.as_str());
+            assert_eq!(!matches!(method, Method::Other(_)), standard, "{http}");
+            assert_eq!(::http::Method::try_from(&method).unwrap(), http);
+            assert_eq!(::http::Method::try_from(method).unwrap(), http);
+        }
+    }
+
+    #[test]
+    fn other_methods() {
+        let method = ::http::Method::try_from(Method::Other("MKCOL".into())).unwrap();
+        assert_eq!(method.as_str(), "MKCOL");
+        // Standard methods spelled out as `Other` still convert.
+        let method = ::http::Method::try_from(Method::Other("GET".into())).unwrap();
+        assert_eq!(method, ::http::Method::GET);
+        for invalid in ["", "BAD METHOD", "GET\n"] {
+            assert!(::http::Method::try_from(Method::Other(invalid.into())).is_err());
+        }
+    }
+
+    #[test]
+    fn schemes() {
+        for (http, expected) in [
+            (::http::uri::Scheme::HTTP, "http"),
+            (::http::uri::Scheme::HTTPS, "https"),
+            ("ws".parse().unwrap(), "ws"),
+            ("coap+tcp".parse().unwrap(), "coap+tcp"),
+        ] {
+            let scheme = Scheme::from(&http);
+            let name = match &scheme {
+                Scheme::Http => "http",
+                Scheme::Https => "https",
+                Scheme::Other(other) => other,
+            };
+            assert_eq!(name, expected);
+            assert_eq!(::http::uri::Scheme::try_from(&scheme).unwrap(), http);
+            assert_eq!(::http::uri::Scheme::try_from(scheme).unwrap(), http);
+        }
+        assert!(matches!(
+            Scheme::from("HTTP".parse::<::http::uri::Scheme>().unwrap()),
+            Scheme::Http
+        ));
+        for invalid in ["ht tp", "a/b", "x://"] {
+            assert!(::http::uri::Scheme::try_from(Scheme::Other(invalid.into())).is_err());
+        }
+    }
+
+    fn entries(entries: &[(&str, &[u8])]) -> Vec<(FieldKey, FieldValue)> {
+        entries
+            .iter()
+            .map(|(name, value)| (String::from(*name), value.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn repeated_headers() {
+        let list = entries(&[
+            ("set-cookie", b"a=1"),
+            ("content-type", b"text/plain"),
+            ("set-cookie", b"b=2"),
+            ("x-binary", b"\x80\xff"),
+        ]);
+        let headers = from_entries(list.clone()).unwrap();
+        assert_eq!(headers.len(), 4);
+        let cookies = headers.get_all("set-cookie").iter().collect::<Vec<_>>();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert_eq!(headers["x-binary"].as_bytes(), b"\x80\xff");
+
+        // `http` groups the values of each name together, in order.
+        let mut round_trip = to_entries(&headers);
+        let mut expected = list;
+        round_trip.sort_by(|a, b| a.0.cmp(&b.0));
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(round_trip, expected);
+    }
+
+    #[test]
+    fn header_names() {
+        // Names are lowercased by `http`, and compared case-insensitively by
+        // the host.
+        let headers = from_entries(entries(&[("Content-Type", b"text/plain")])).unwrap();
+        assert_eq!(
+            to_entries(&headers),
+            entries(&[("content-type", b"text/plain")])
+        );
+    }
+
+    #[test]
+    fn invalid_headers() {
+        for invalid in [
+            entries(&[("bad name", b"x")]),
+            entries(&[("", b"x")]),
+            entries(&[("x-newline", b"a\nb")]),
+            entries(&[("x-nul", b"a\0b")]),
+        ] {
+            assert!(matches!(
+                from_entries(invalid),
+                Err(HeaderError::InvalidSyntax)
+            ));
+        }
+    }
+
+    #[test]
+    fn forbidden_headers() {
+        // Whether a header is forbidden is up to the host, so headers such as
+        // `connection` or `host` are passed through unchanged.
+        let list = entries(&[("connection", b"close"), ("host", b"example.com")]);
+        let headers = from_entries(list.clone()).unwrap();
+        assert_eq!(to_entries(&headers), list);
+    }
+}
@@ -0,0 +1,565 @@
+//! A header map with case-insensitive lookup and typed accessors, which
+//! converts to and from [`Fields`].
+//!
+//! [`Fields`] is a resource owned by the host, so every lookup is a call into
+//! the host, names are compared case-sensitively, and fields attached to a
+//! request or response can't be changed anymore. A [`HeaderMap`] is a plain
+//! Rust copy of the headers which can be inspected and modified freely, and
+//! turned into new [`Fields`] when needed.
+//!
+//! ```no_run
+//! use wasi::http::headers::HeaderMap;
+//! use wasi::http::types::{IncomingRequest, OutgoingResponse};
+//!
+//! # fn handle(request: IncomingRequest) -> Result<(), wasi::http::headers::Error> {
+//! let headers = HeaderMap::from_fields(&request.headers());
+//! if headers.content_type() == Some("application/json") {
+//!     // ...
+//! }
+//!
+//! let mut headers = HeaderMap::new();
+//! headers.set_content_type("text/plain");
+//! headers.insert("cache-control", "no-store");
+//! let response = OutgoingResponse::new(headers.to_fields()?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::http::types::{FieldKey, FieldValue, Fields, HeaderError};
+use std::fmt;
+use std::format;
+use std::string::{String, ToString};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+/// An ordered list of headers, where names are compared case-insensitively.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(FieldKey, FieldValue)>,
+}
+
+impl HeaderMap {
+    /// Creates an empty header map.
+    pub fn new() -> HeaderMap {
+        HeaderMap::default()
+    }
+
+    /// Copies every entry of `fields`.
+    pub fn from_fields(fields: &Fields) -> HeaderMap {
+        HeaderMap {
+            entries: fields.entries(),
+        }
+    }
+
+    /// Creates new, mutable [`Fields`] with every entry of this map.
+    pub fn to_fields(&self) -> Result<Fields, Error> {
+        Fields::from_list(&self.entries).map_err(|e| Error::from_header_error(e, None))
+    }
+
+    /// Replaces the values of every name in this map in `fields`, leaving
+    /// other names unchanged.
+    ///
+    /// Fails with [`Error::Immutable`] if `fields` belong to a request or
+    /// response, in which case they can't be changed anymore.
+    pub fn write_to(&self, fields: &Fields) -> Result<(), Error> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in &self.entries {
+            if names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+            names.push(name);
+            let values = self.get_all(name).map(<[u8]>::to_vec).collect::<Vec<_>>();
+            fields
+                .set(name, &values)
+                .map_err(|e| Error::from_header_error(e, Some(name)))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Returns the first value of `name` if it's valid UTF-8, with leading
+    /// and trailing whitespace removed.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        let value = core::str::from_utf8(self.get(name)?).ok()?;
+        Some(value.trim())
+    }
+
+    /// Returns every value of `name`, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Returns whether there's any value of `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to `value`, replacing any previous values.
+    pub fn insert(&mut self, name: &str, value: impl Into<FieldValue>) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    /// Adds `value` to the values of `name`.
+    pub fn append(&mut self, name: &str, value: impl Into<FieldValue>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Removes every value of `name`, returning whether there were any.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self.entries.len() != len
+    }
+
+    /// Returns the number of entries, counting every value separately.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over every entry, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+    }
+
+    /// Returns the `content-type` header.
+    pub fn content_type(&self) -> Option<&str> {
+        self.get_str("content-type")
+    }
+
+    /// Sets the `content-type` header.
+    pub fn set_content_type(&mut self, content_type: &str) {
+        self.insert("content-type", content_type);
+    }
+
+    /// Returns the `content-length` header, or `None` if it's missing or
+    /// isn't a number.
+    pub fn content_length(&self) -> Option<u64> {
+        self.get_str("content-length")?.parse().ok()
+    }
+
+    /// Sets the `content-length` header.
+    pub fn set_content_length(&mut self, len: u64) {
+        self.insert("content-length", len.to_string());
+    }
+
+    /// Returns the `authorization` header.
+    pub fn authorization(&self) -> Option<&str> {
+        self.get_str("authorization")
+    }
+
+    /// Returns the token of a `Bearer` `authorization` header.
+    pub fn bearer_token(&self) -> Option<&str> {
+        let (scheme, token) = self.authorization()?.split_once(' ')?;
+        let token = token.trim_start();
+        (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+    }
+
+    /// Returns the directives of the `cache-control` headers, each with its
+    /// value if it has one, such as `("max-age", Some("60"))`.
+    pub fn cache_control(&self) -> Vec<(&str, Option<&str>)> {
+        self.get_all("cache-control")
+            .filter_map(|value| core::str::from_utf8(value).ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+            .map(|directive| match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive, None),
+            })
+            .collect()
+    }
+
+    /// Returns the `max-age` directive of the `cache-control` headers.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.cache_control()
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("max-age"))
+            .and_then(|(_, value)| value?.parse().ok())
+            .map(Duration::from_secs)
+    }
+
+    /// Returns the `etag` header, including its quotes and weak `W/` prefix
+    /// if it has one.
+    pub fn etag(&self) -> Option<&str> {
+        self.get_str("etag")
+    }
+
+    /// Returns the `date` header, or `None` if it's missing or isn't an
+    /// HTTP date such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+    pub fn date(&self) -> Option<SystemTime> {
+        parse_http_date(self.get_str("date")?)
+    }
+
+    /// Sets the `date` header to `time`.
+    pub fn set_date(&mut self, time: SystemTime) {
+        self.insert("date", format_http_date(time));
+    }
+}
+
+impl From<&Fields> for HeaderMap {
+    fn from(fields: &Fields) -> HeaderMap {
+        HeaderMap::from_fields(fields)
+    }
+}
+
+impl TryFrom<&HeaderMap> for Fields {
+    type Error = Error;
+
+    fn try_from(headers: &HeaderMap) -> Result<Fields, Error> {
+        headers.to_fields()
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (FieldKey, FieldValue);
+    type IntoIter = std::vec::IntoIter<(FieldKey, FieldValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl FromIterator<(FieldKey, FieldValue)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (FieldKey, FieldValue)>>(iter: I) -> HeaderMap {
+        HeaderMap {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<(FieldKey, FieldValue)> for HeaderMap {
+    fn extend<I: IntoIterator<Item = (FieldKey, FieldValue)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+    }
+}
+
+/// Errors from converting a [`HeaderMap`] into [`Fields`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A header name or value isn't valid syntax.
+    InvalidSyntax {
+        /// The name of the header, if it's known.
+        name: Option<String>,
+    },
+    /// A header is forbidden by the host, such as `host` or `connection`.
+    Forbidden {
+        /// The name of the header, if it's known.
+        name: Option<String>,
+    },
+    /// The fields belong to a request or response and can't be changed
+    /// anymore.
+    Immutable,
+}
+
+impl Error {
+    fn from_header_error(e: HeaderError, name: Option<&str>) -> Error {
+        let name = name.map(String::from);
+        match e {
+            HeaderError::InvalidSyntax => Error::InvalidSyntax { name },
+            HeaderError::Forbidden => Error::Forbidden { name },
+            HeaderError::Immutable => Error::Immutable,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSyntax { name: Some(name) } => {
+                write!(f, "header `{name}` has invalid syntax")
+            }
+            Error::InvalidSyntax { name: None } => f.write_str("a header has invalid syntax"),
+            Error::Forbidden { name: Some(name) } => write!(f, "header `{name}` is forbidden"),
+            Error::Forbidden { name: None } => f.write_str("a header is forbidden"),
+            Error::Immutable => f.write_str(
+                "headers can't be modified after being attached to a request or response",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Formats `time` as an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn format_http_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let days = secs / 86400;
+    let secs = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        MONTHS[month as usize - 1],
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+    )
+}
+
+/// Parses an IMF-fixdate, such as `Sun, 06 Nov 1994 08:49:37 GMT`, whose
+/// weekday must be the one of its date.
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let (weekday, rest) = date.split_once(", ")?;
+    let mut parts = rest.split(' ');
+    let day = parse_digits(parts.next()?, 2)?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year = parse_digits(parts.next()?, 4)?;
+    let mut time = parts.next()?.split(':');
+    let hour = parse_digits(time.next()?, 2)?;
+    let minute = parse_digits(time.next()?, 2)?;
+    let second = parse_digits(time.next()?, 2)?;
+    if parts.next()? != "GMT" || parts.next().is_some() || time.next().is_some() {
+        return None;
+    }
+    if year < 1970
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if DAYS[(days % 7) as usize] != weekday {
+        return None;
+    }
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Parses a number of exactly `len` ASCII digits.
+fn parse_digits(digits: &str, len: usize) -> Option<u64> {
+    if digits.len() != len || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Returns the number of days in `month` of `year`.
+// `u64::is_multiple_of` is too recent to be used here.
+#[allow(clippy::manual_is_multiple_of)]
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the year, month and day of `days` since the Unix epoch.
+///
+/// This is Howard Hinnant's `civil_from_days` for dates after the epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the days since the Unix epoch of a date after it.
+///
+/// This is Howard Hinnant's `days_from_civil` for dates after the epoch.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn headers(entries: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(name, value.as_bytes().to_vec());
+        }
+        headers
+    }
+
+    #[test]
+    fn known_dates() {
+        let vectors = [
+            ("Thu, 01 Jan 1970 00:00:00 GMT", 0),
+            ("Sun, 06 Nov 1994 08:49:37 GMT", 784111777),
+            ("Tue, 29 Feb 2000 12:00:00 GMT", 951825600),
+            ("Sun, 09 Sep 2001 01:46:40 GMT", 1000000000),
+            ("Tue, 19 Jan 2038 03:14:08 GMT", 2147483648),
+            ("Thu, 29 Feb 2024 23:59:59 GMT", 1709251199),
+            ("Fri, 31 Dec 9999 23:59:59 GMT", 253402300799),
+        ];
+        for (date, time) in vectors {
+            assert_eq!(format_http_date(secs(time)), date);
+            assert_eq!(parse_http_date(date), Some(secs(time)), "{date}");
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        // Every day over a few leap cycles, at a different time of day.
+        for day in 0..(366 * 12) {
+            let time = secs(day * 86400 + day * 3607 % 86400);
+            assert_eq!(parse_http_date(&format_http_date(time)), Some(time));
+        }
+        // Sub-second precision is dropped.
+        let time = secs(784111777) + Duration::from_millis(999);
+        assert_eq!(
+            parse_http_date(&format_http_date(time)),
+            Some(secs(784111777))
+        );
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let invalid = [
+            "",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 06 Nov 1994 08:49:37 GMT extra",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Xyz, 06 Nov 1994 08:49:37 GMT",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 94 08:49:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1994 08:49:37:00 GMT",
+            "Sun, +6 Nov 1994 08:49:37 GMT",
+            "Wed, 31 Dec 1969 23:59:59 GMT",
+            "Sun, 00 Nov 1994 08:49:37 GMT",
+            "Sun, 31 Nov 1994 08:49:37 GMT",
+            "Sun, 32 Dec 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:60:00 GMT",
+            "Sun, 06 Nov 1994 08:49:61 GMT",
+            "Sat, 31 Feb 2024 00:00:00 GMT",
+            "Thu, 30 Feb 2024 00:00:00 GMT",
+            "Thu, 29 Feb 2023 00:00:00 GMT",
+            "Thu, 29 Feb 1900 00:00:00 GMT",
+            "Fri, 31 Dec 99999 23:59:59 GMT",
+            // The weekday must match the date.
+            "Mon, 01 Jan 2025 00:00:00 GMT",
+            "Sat, 06 Nov 1994 08:49:37 GMT",
+            "Thu, 02 Jan 1970 00:00:00 GMT",
+        ];
+        for date in invalid {
+            assert_eq!(parse_http_date(date), None, "{date}");
+        }
+    }
+
+    #[test]
+    fn month_lengths() {
+        let lengths = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+        for (month, length) in (1..).zip(lengths) {
+            assert_eq!(days_in_month(2023, month), length);
+        }
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+    }
+
+    #[test]
+    fn date_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(headers.date(), None);
+        headers.set_date(secs(784111777));
+        assert_eq!(
+            headers.get_str("date"),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+        assert_eq!(headers.date(), Some(secs(784111777)));
+    }
+
+    #[test]
+    fn cache_control() {
+        let headers = headers(&[
+            ("Cache-Control", "public, max-age=60"),
+            (
+                "cache-control",
+                " no-cache=\"set-cookie\" ,, must-revalidate",
+            ),
+        ]);
+        assert_eq!(
+            headers.cache_control(),
+            [
+                ("public", None),
+                ("max-age", Some("60")),
+                ("no-cache", Some("set-cookie")),
+                ("must-revalidate", None),
+            ]
+        );
+        assert_eq!(HeaderMap::new().cache_control(), []);
+    }
+
+    #[test]
+    fn max_age() {
+        let max_age = |value| headers(&[("cache-control", value)]).max_age();
+        assert_eq!(max_age("max-age=60"), Some(Duration::from_secs(60)));
+        assert_eq!(max_age("no-store, MAX-AGE=0"), Some(Duration::ZERO));
+        assert_eq!(max_age("max-age=\"3600\""), Some(Duration::from_secs(3600)));
+        assert_eq!(max_age("max-age"), None);
+        assert_eq!(max_age("max-age=-1"), None);
+        assert_eq!(max_age("max-age=soon"), None);
+        assert_eq!(max_age("s-maxage=60"), None);
+        assert_eq!(HeaderMap::new().max_age(), None);
+    }
+
+    #[test]
+    fn bearer_token() {
+        let token = |value| {
+            headers(&[("authorization", value)])
+                .bearer_token()
+                .map(String::from)
+        };
+        assert_eq!(token("Bearer abc.def"), Some("abc.def".into()));
+        assert_eq!(token("bearer abc"), Some("abc".into()));
+        assert_eq!(token("BEARER   abc"), Some("abc".into()));
+        assert_eq!(token("Bearer "), None);
+        assert_eq!(token("Bearer"), None);
+        assert_eq!(token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(token("Bearerabc"), None);
+        assert_eq!(HeaderMap::new().bearer_token(), None);
+    }
+}
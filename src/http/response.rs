@@ -0,0 +1,327 @@
+//! Building and sending responses to incoming requests.
+//!
+//! Responding to a request through a [`ResponseOutparam`] takes several
+//! steps: building the [`Fields`] of the headers, creating the
+//! [`OutgoingResponse`] and setting its status, taking its body, setting the
+//! outparam, and finally writing and finishing the body. A [`Response`]
+//! describes the whole response up front, and [`Response::send`] takes care
+//! of all of those steps.
+//!
+//! ```no_run
+//! use wasi::http::response::Response;
+//! use wasi::http::types::{ErrorCode, IncomingRequest, ResponseOutparam};
+//!
+//! fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+//!     let response = match request.path_with_query().as_deref() {
+//!         Some("/") => Response::text("hello"),
+//!         Some("/old") => Response::redirect(308, "/"),
+//!         Some("/teapot") => Response::builder()
+//!             .status(418)
+//!             .header("content-type", "text/plain")
+//!             .body("I'm a teapot"),
+//!         Some("/broken") => {
+//!             // Failures which aren't a response at all are reported with an
+//!             // error code instead.
+//!             let code = ErrorCode::InternalError(Some("broken".into()));
+//!             return ResponseOutparam::set(response_out, Err(code));
+//!         }
+//!         _ => Response::empty(404),
+//!     };
+//!     let _ = response.send(response_out);
+//! }
+//! ```
+
+use crate::http::body::BodyWriter;
+use crate::http::headers::HeaderMap;
+use crate::http::types::{
+    ErrorCode, FieldKey, FieldValue, Fields, OutgoingResponse, ResponseOutparam, StatusCode,
+};
+use std::format;
+use std::io::{self, Write};
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// A response which is ready to be sent, created with
+/// [`builder`](Response::builder) or one of the shortcuts.
+#[derive(Clone, Debug)]
+pub struct Response {
+    status: StatusCode,
+    headers: Vec<(FieldKey, FieldValue)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Starts building a response with status `200 OK` and no headers.
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder {
+            status: 200,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Creates a `200 OK` response with a `text/plain` body.
+    pub fn text(body: impl Into<String>) -> Response {
+        Response::builder()
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// Creates a `200 OK` response with a `text/html` body.
+    pub fn html(body: impl Into<String>) -> Response {
+        Response::builder()
+            .header("content-type", "text/html; charset=utf-8")
+            .body(body.into())
+    }
+
+    /// Creates a `200 OK` response with `value` serialized as its
+    /// `application/json` body.
+    ///
+    /// Values which can't be serialized, such as maps with non-string keys,
+    /// result in a `500 Internal Server Error` response instead.
+    ///
+    /// ```no_run
+    /// use wasi::http::response::Response;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Status {
+    ///     healthy: bool,
+    /// }
+    ///
+    /// let response = Response::json(&Status { healthy: true });
+    /// assert_eq!(response.body(), br#"{"healthy":true}"#);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(value: &T) -> Response {
+        match serde_json::to_vec(value) {
+            Ok(body) => Response::builder()
+                .header("content-type", "application/json")
+                .body(body),
+            Err(e) => Response::builder()
+                .status(500)
+                .header("content-type", "text/plain; charset=utf-8")
+                .body(format!("failed to serialize response: {e}")),
+        }
+    }
+
+    /// Creates a response with the given redirect `status`, such as `302` or
+    /// `308`, to `location`.
+    pub fn redirect(status: StatusCode, location: &str) -> Response {
+        Response::builder()
+            .status(status)
+            .header("location", location)
+            .body(Vec::new())
+    }
+
+    /// Creates a response with the given `status` and no body.
+    pub fn empty(status: StatusCode) -> Response {
+        Response::builder().status(status).body(Vec::new())
+    }
+
+    /// Returns the status code of this response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of this response, in the order they were added.
+    pub fn headers(&self) -> &[(FieldKey, FieldValue)] {
+        &self.headers
+    }
+
+    /// Returns the body of this response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Creates the [`OutgoingResponse`] for this response, along with its
+    /// body which is yet to be written.
+    ///
+    /// A `content-length` header is added unless one was set already, or the
+    /// status doesn't allow one.
+    pub fn build(self) -> Result<(OutgoingResponse, PendingBody), ErrorCode> {
+        let mut headers = self.headers;
+        if let Some(value) = content_length(self.status, &headers, &self.body) {
+            headers.push(("content-length".into(), value));
+        }
+        let headers = Fields::from_list(&headers)
+            .map_err(|e| ErrorCode::InternalError(Some(format!("invalid header: {e}"))))?;
+        let response = OutgoingResponse::new(headers);
+        response.set_status_code(self.status).map_err(|()| {
+            ErrorCode::InternalError(Some(format!("invalid status code {}", self.status)))
+        })?;
+        let writer = BodyWriter::for_response(&response).map_err(error_code)?;
+        Ok((
+            response,
+            PendingBody {
+                writer,
+                body: self.body,
+            },
+        ))
+    }
+
+    /// Sends this response through `response_out` and writes its body.
+    ///
+    /// If the response can't be built then `response_out` is set to the
+    /// error instead. Either way the error is returned, although the host
+    /// has already been told about it.
+    pub fn send(self, response_out: ResponseOutparam) -> Result<(), ErrorCode> {
+        match self.build() {
+            Ok((response, body)) => {
+                ResponseOutparam::set(response_out, Ok(response));
+                body.write()
+            }
+            Err(code) => {
+                ResponseOutparam::set(response_out, Err(code.clone()));
+                Err(code)
+            }
+        }
+    }
+}
+
+/// A response which is being built, created with [`Response::builder`].
+#[derive(Clone, Debug)]
+pub struct ResponseBuilder {
+    status: StatusCode,
+    headers: Vec<(FieldKey, FieldValue)>,
+}
+
+impl ResponseBuilder {
+    /// Sets the status code of the response.
+    pub fn status(mut self, status: StatusCode) -> ResponseBuilder {
+        self.status = status;
+        self
+    }
+
+    /// Appends a header to the response.
+    ///
+    /// Invalid or forbidden headers are reported when the response is built.
+    pub fn header(mut self, name: &str, value: impl Into<Vec<u8>>) -> ResponseBuilder {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Appends every entry of `headers` to the response.
+    pub fn headers(mut self, headers: HeaderMap) -> ResponseBuilder {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Finishes building the response with `body`.
+    pub fn body(self, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status: self.status,
+            headers: self.headers,
+            body: body.into(),
+        }
+    }
+}
+
+/// The body of a response built with [`Response::build`], which is yet to be
+/// written.
+pub struct PendingBody {
+    writer: BodyWriter,
+    body: Vec<u8>,
+}
+
+impl PendingBody {
+    /// Writes and finishes the body.
+    ///
+    /// This should be called after the response has been set on its
+    /// [`ResponseOutparam`], since the host may not accept the whole body
+    /// before then.
+    pub fn write(mut self) -> Result<(), ErrorCode> {
+        self.writer.write_all(&self.body).map_err(error_code)?;
+        self.writer.finish(None)
+    }
+}
+
+/// Returns the value of the `content-length` header to add to a response
+/// with `status`, `headers` and `body`, unless it has one already or the
+/// status doesn't allow one.
+fn content_length(
+    status: StatusCode,
+    headers: &[(FieldKey, FieldValue)],
+    body: &[u8],
+) -> Option<FieldValue> {
+    let has_content_length = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-length"));
+    if has_content_length || matches!(status, 100..=199 | 204 | 304) {
+        return None;
+    }
+    Some(body.len().to_string().into_bytes())
+}
+
+/// Converts an error from writing a body into the [`ErrorCode`] behind it, if
+/// there is one.
+fn error_code(e: io::Error) -> ErrorCode {
+    match e.get_ref().and_then(|e| e.downcast_ref::<ErrorCode>()) {
+        Some(code) => code.clone(),
+        None => ErrorCode::InternalError(Some(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_lengths() {
+        let cases: [(StatusCode, &[u8], Option<&str>); 10] = [
+            (200, b"hello", Some("5")),
+            (200, b"", Some("0")),
+            (404, b"not found", Some("9")),
+            (205, b"", Some("0")),
+            (308, b"", Some("0")),
+            // Informational, `204 No Content` and `304 Not Modified`
+            // responses can't have one.
+            (100, b"", None),
+            (101, b"", None),
+            (199, b"", None),
+            (204, b"", None),
+            (304, b"", None),
+        ];
+        for (status, body, expected) in cases {
+            let value = content_length(status, &[], body);
+            assert_eq!(value.as_deref(), expected.map(str::as_bytes), "{status}");
+        }
+    }
+
+    #[test]
+    fn explicit_content_lengths() {
+        for name in ["content-length", "Content-Length", "CONTENT-LENGTH"] {
+            let headers = [(name.into(), b"3".to_vec())];
+            assert_eq!(content_length(200, &headers, b"hello"), None, "{name}");
+        }
+        let headers = [("content-type".into(), b"text/plain".to_vec())];
+        assert_eq!(content_length(200, &headers, b"hello"), Some(b"5".to_vec()));
+    }
+
+    #[test]
+    fn shortcuts() {
+        let response = Response::text("hello");
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), b"hello");
+        assert_eq!(
+            response.headers(),
+            [("content-type".into(), b"text/plain; charset=utf-8".to_vec())]
+        );
+
+        let response = Response::redirect(308, "/new");
+        assert_eq!(response.status(), 308);
+        assert_eq!(response.body(), b"");
+        assert_eq!(response.headers(), [("location".into(), b"/new".to_vec())]);
+
+        let response = Response::empty(204);
+        assert_eq!(response.status(), 204);
+        assert!(response.headers().is_empty());
+        assert!(response.body().is_empty());
+    }
+
+    #[test]
+    fn error_codes() {
+        let code = error_code(io::Error::other(ErrorCode::HttpResponseBodySize(Some(3))));
+        assert!(matches!(code, ErrorCode::HttpResponseBodySize(Some(3))));
+        let code = error_code(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(matches!(code, ErrorCode::InternalError(Some(_))));
+    }
+}
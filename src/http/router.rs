@@ -0,0 +1,659 @@
+//! Routing of incoming requests to handlers based on their method and path.
+//!
+//! A [`Router`] is a list of routes, each made of a method and a path
+//! pattern, which dispatches an [`IncomingRequest`] to the handler of the
+//! first route that matches it. Requests which don't match any route are
+//! answered automatically with a `404 Not Found`, or a
+//! `405 Method Not Allowed` if the path matched a route with another method.
+//! `HEAD` requests are handled by `GET` routes unless a `HEAD` route matches.
+//!
+//! Path patterns are made of `/`-separated segments, each of which is one of:
+//!
+//! * A literal, such as `users`, which must match exactly.
+//! * A parameter, such as `:id`, which matches any single segment.
+//! * A wildcard, such as `*rest` or `*`, which matches all remaining
+//!   segments. Wildcards may only be used as the last segment of a pattern.
+//!
+//! Matched parameters, the query string and headers are available through
+//! the [`Request`] passed to handlers.
+//!
+//! ```no_run
+//! use wasi::http::router::{Request, Router, Routes};
+//! use wasi::http::types::ResponseOutparam;
+//!
+//! struct MyRoutes;
+//!
+//! impl Routes for MyRoutes {
+//!     fn router() -> Router {
+//!         Router::new()
+//!             .get("/users/:id", get_user)
+//!             .post("/users", create_user)
+//!             .get("/static/*path", serve_static)
+//!     }
+//! }
+//!
+//! fn get_user(request: Request, response_out: ResponseOutparam) {
+//!     let id = request.param("id").unwrap();
+//!     let verbose = request.query_param("verbose").is_some();
+//!     // ...
+//! #   drop((id, verbose, response_out));
+//! }
+//! # fn create_user(request: Request, response_out: ResponseOutparam) {}
+//! # fn serve_static(request: Request, response_out: ResponseOutparam) {}
+//!
+//! wasi::http::router::export!(MyRoutes);
+//! ```
+
+use crate::http::response::Response;
+use crate::http::types::{Headers, IncomingRequest, Method, ResponseOutparam};
+use std::boxed::Box;
+use std::string::String;
+use std::vec::Vec;
+
+/// A type which can be exported as a `wasi:http/incoming-handler` through
+/// [`export!`](crate::http::router::export) by describing its routes.
+pub trait Routes {
+    /// Returns the router used to handle an incoming request.
+    ///
+    /// This is called once for every incoming request.
+    fn router() -> Router;
+}
+
+type Handler = Box<dyn Fn(Request, ResponseOutparam)>;
+
+/// A list of routes dispatching incoming requests to handlers.
+///
+/// Routes are tried in the order they were added, and the first one whose
+/// method and path pattern match a request handles it.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    /// The method this route matches, or `None` for all methods.
+    method: Option<Method>,
+    pattern: Vec<Segment>,
+    handler: Handler,
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Router {
+    /// Creates a router without any routes.
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Adds a route for requests with the given `method` whose path matches
+    /// `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` has a wildcard segment which isn't its last.
+    pub fn route<H>(self, method: Method, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.add(Some(method), pattern, Box::new(handler))
+    }
+
+    /// Adds a route for requests with any method whose path matches `pattern`.
+    pub fn any<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.add(None, pattern, Box::new(handler))
+    }
+
+    /// Adds a route for `GET` requests whose path matches `pattern`.
+    pub fn get<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    /// Adds a route for `POST` requests whose path matches `pattern`.
+    pub fn post<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    /// Adds a route for `PUT` requests whose path matches `pattern`.
+    pub fn put<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    /// Adds a route for `PATCH` requests whose path matches `pattern`.
+    pub fn patch<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.route(Method::Patch, pattern, handler)
+    }
+
+    /// Adds a route for `DELETE` requests whose path matches `pattern`.
+    pub fn delete<H>(self, pattern: &str, handler: H) -> Router
+    where
+        H: Fn(Request, ResponseOutparam) + 'static,
+    {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    fn add(mut self, method: Option<Method>, pattern: &str, handler: Handler) -> Router {
+        let segments = segments(pattern).collect::<Vec<_>>();
+        let mut pattern = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            pattern.push(if let Some(name) = segment.strip_prefix(':') {
+                Segment::Param(name.into())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                assert!(
+                    i == segments.len() - 1,
+                    "wildcards must be the last segment of a pattern"
+                );
+                Segment::Wildcard(name.into())
+            } else {
+                Segment::Literal((*segment).into())
+            });
+        }
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler,
+        });
+        self
+    }
+
+    /// Dispatches `request` to the handler of the first matching route.
+    ///
+    /// If no route matches then `response_out` is set to an empty response
+    /// with status `404 Not Found`, or `405 Method Not Allowed` along with an
+    /// `allow` header if a route for another method matched the path.
+    ///
+    /// `HEAD` requests which don't match a `HEAD` route fall back to the first
+    /// matching `GET` route. Its handler sees the `HEAD` method through
+    /// [`Request::method`] and may skip writing the body.
+    pub fn handle(&self, request: IncomingRequest, response_out: ResponseOutparam) {
+        let path_with_query = request.path_with_query().unwrap_or_default();
+        let (path, query) = match path_with_query.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (&path_with_query[..], None),
+        };
+
+        let response = match self.dispatch(&request.method(), path) {
+            Dispatch::Route(route, params) => {
+                let request = Request {
+                    params,
+                    path: percent_decode(path, false),
+                    query: query.map(String::from),
+                    inner: request,
+                };
+                return (route.handler)(request, response_out);
+            }
+            Dispatch::NotFound => Response::empty(404),
+            Dispatch::MethodNotAllowed(allowed) => Response::builder()
+                .status(405)
+                .header("allow", allowed.join(", "))
+                .body(Vec::new()),
+        };
+        // There's nobody to report an error to at this point, the host will see
+        // the response as failed anyway.
+        let _ = response.send(response_out);
+    }
+
+    /// Finds the route for a request with `method` and `path`.
+    fn dispatch(&self, method: &Method, path: &str) -> Dispatch<'_> {
+        let is_head = method_name(method) == "HEAD";
+        let mut get_fallback = None;
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.matches(path) else {
+                continue;
+            };
+            match &route.method {
+                Some(m) if !same_method(m, method) => {
+                    if is_head && get_fallback.is_none() && method_name(m) == "GET" {
+                        get_fallback = Some((route, params));
+                    } else if !allowed.contains(&method_name(m)) {
+                        allowed.push(method_name(m));
+                    }
+                }
+                _ => return Dispatch::Route(route, params),
+            }
+        }
+
+        if let Some((route, params)) = get_fallback {
+            return Dispatch::Route(route, params);
+        }
+        if allowed.is_empty() {
+            return Dispatch::NotFound;
+        }
+        // Routes for `GET` also answer `HEAD` requests.
+        if allowed.contains(&"GET") && !allowed.contains(&"HEAD") {
+            allowed.push("HEAD");
+        }
+        Dispatch::MethodNotAllowed(allowed)
+    }
+}
+
+/// The outcome of looking up the route for a request.
+enum Dispatch<'a> {
+    /// The route matched, with the parameters captured from the path.
+    Route(&'a Route, Vec<(String, String)>),
+    /// No route matched the path.
+    NotFound,
+    /// Only routes for other methods, which are listed, matched the path.
+    MethodNotAllowed(Vec<&'a str>),
+}
+
+impl Route {
+    /// Returns the parameters captured from `path` if it matches this route.
+    fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let segments = segments(path).collect::<Vec<_>>();
+        let mut params = Vec::new();
+        for (i, pattern) in self.pattern.iter().enumerate() {
+            match pattern {
+                Segment::Literal(literal) => {
+                    if percent_decode(segments.get(i)?, false) != *literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.push((name.clone(), percent_decode(segments.get(i)?, false)));
+                }
+                Segment::Wildcard(name) => {
+                    let rest = segments.get(i..).unwrap_or_default().join("/");
+                    params.push((name.clone(), percent_decode(&rest, false)));
+                    return Some(params);
+                }
+            }
+        }
+        (segments.len() == self.pattern.len()).then_some(params)
+    }
+}
+
+/// Returns the non-empty `/`-separated segments of `path`.
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+/// Returns whether two methods are the same, which is case-sensitive like
+/// the rest of HTTP methods, so that `Method::Other("get")` isn't `GET`.
+fn same_method(a: &Method, b: &Method) -> bool {
+    method_name(a) == method_name(b)
+}
+
+fn method_name(method: &Method) -> &str {
+    match method {
+        Method::Get => "GET",
+        Method::Head => "HEAD",
+        Method::Post => "POST",
+        Method::Put => "PUT",
+        Method::Delete => "DELETE",
+        Method::Connect => "CONNECT",
+        Method::Options => "OPTIONS",
+        Method::Trace => "TRACE",
+        Method::Patch => "PATCH",
+        Method::Other(other) => other,
+    }
+}
+
+/// An incoming request which matched a route of a [`Router`].
+pub struct Request {
+    params: Vec<(String, String)>,
+    path: String,
+    query: Option<String>,
+    inner: IncomingRequest,
+}
+
+impl Request {
+    /// Returns the value of the path parameter `name`, such as `id` for a
+    /// route with the pattern `/users/:id`.
+    ///
+    /// Named wildcards, such as `*rest`, capture all remaining segments of
+    /// the path joined with `/`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| &v[..])
+    }
+
+    /// Returns every path parameter, in the order of the route's pattern.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Returns the method of the request.
+    pub fn method(&self) -> Method {
+        self.inner.method()
+    }
+
+    /// Returns the percent-decoded path of the request, without the query.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the raw query string of the request, without the leading `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// Returns the decoded key/value pairs of the query string.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        let Some(query) = &self.query else {
+            return Vec::new();
+        };
+        query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key, true), percent_decode(value, true))
+            })
+            .collect()
+    }
+
+    /// Returns the decoded value of the first query parameter named `name`.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query_pairs()
+            .into_iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the headers of the request.
+    ///
+    /// The returned fields are a child resource of the request and must be
+    /// dropped before it.
+    pub fn headers(&self) -> Headers {
+        self.inner.headers()
+    }
+
+    /// Returns the first value of the header `name`.
+    pub fn header(&self, name: &str) -> Option<Vec<u8>> {
+        self.headers().get(&name.into()).into_iter().next()
+    }
+
+    /// Returns a reference to the underlying request, for example to consume
+    /// its body.
+    pub fn inner(&self) -> &IncomingRequest {
+        &self.inner
+    }
+
+    /// Returns the underlying request.
+    pub fn into_inner(self) -> IncomingRequest {
+        self.inner
+    }
+}
+
+/// Decodes `%XX` escapes in `s`, and `+` as a space if `plus_as_space` is
+/// set. Invalid escapes are left as is.
+fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Generate an exported instance of the `wasi:http/proxy` world which
+/// dispatches requests through a [`Router`].
+///
+/// This macro takes an argument which is a type implementing [`Routes`],
+/// implements
+/// [`exports::http::incoming_handler::Guest`](crate::exports::http::incoming_handler::Guest)
+/// for it, and exports it with
+/// [`wasi::http::proxy::export!`](crate::http::proxy::export).
+///
+/// ```
+/// use wasi::http::router::{Router, Routes};
+///
+/// struct MyRoutes;
+///
+/// impl Routes for MyRoutes {
+///     fn router() -> Router {
+///         Router::new()
+///             // ...
+///     }
+/// }
+///
+/// wasi::http::router::export!(MyRoutes);
+/// ```
+#[doc(inline)]
+pub use crate::_export_router as export;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _export_router {
+    ($ty:ident) => {
+        impl $crate::exports::http::incoming_handler::Guest for $ty {
+            fn handle(
+                request: $crate::http::types::IncomingRequest,
+                response_out: $crate::http::types::ResponseOutparam,
+            ) {
+                <$ty as $crate::http::router::Routes>::router().handle(request, response_out)
+            }
+        }
+
+        $crate::http::proxy::export!($ty);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn router() -> Router {
+        let handler = |_: Request, _: ResponseOutparam| {};
+        Router::new()
+            .get("/users", handler)
+            .post("/users", handler)
+            .get("/users/:id", handler)
+            .delete("/users/:id", handler)
+            .get("/files/*path", handler)
+            .route(Method::Head, "/health", handler)
+            .any("/any/:x", handler)
+    }
+
+    /// Returns the index of the route `method` and `path` dispatch to.
+    fn route(router: &Router, method: Method, path: &str) -> Option<usize> {
+        match router.dispatch(&method, path) {
+            Dispatch::Route(route, _) => router.routes.iter().position(|r| core::ptr::eq(r, route)),
+            _ => None,
+        }
+    }
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        Router::new()
+            .any(pattern, |_, _| {})
+            .routes
+            .remove(0)
+            .matches(path)
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn literal_segments() {
+        assert_eq!(params("/a/b", "/a/b"), Some(Vec::new()));
+        assert_eq!(params("/a/b", "a//b/"), Some(Vec::new()));
+        assert_eq!(params("/a/b", "/a"), None);
+        assert_eq!(params("/a/b", "/a/b/c"), None);
+        assert_eq!(params("/a/b", "/a/B"), None);
+        assert_eq!(params("/", "/"), Some(Vec::new()));
+        assert_eq!(params("/", "/a"), None);
+        // Literals are compared with the decoded segment.
+        assert_eq!(params("/a b", "/a%20b"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn param_segments() {
+        assert_eq!(
+            params("/users/:id/posts/:post", "/users/42/posts/7"),
+            Some(pairs(&[("id", "42"), ("post", "7")]))
+        );
+        assert_eq!(
+            params("/users/:id", "/users/a%2Fb"),
+            Some(pairs(&[("id", "a/b")]))
+        );
+        assert_eq!(params("/users/:id", "/users"), None);
+        assert_eq!(params("/users/:id", "/users/1/2"), None);
+    }
+
+    #[test]
+    fn wildcard_segments() {
+        assert_eq!(
+            params("/files/*path", "/files/a/b%20c/d"),
+            Some(pairs(&[("path", "a/b c/d")]))
+        );
+        assert_eq!(
+            params("/files/*path", "/files"),
+            Some(pairs(&[("path", "")]))
+        );
+        assert_eq!(params("/files/*", "/files/x"), Some(pairs(&[("", "x")])));
+        assert_eq!(params("/files/*path", "/other/x"), None);
+    }
+
+    #[test]
+    #[should_panic = "wildcards must be the last segment"]
+    fn wildcard_not_last() {
+        Router::new().get("/*rest/x", |_, _| {});
+    }
+
+    #[test]
+    fn dispatch_to_first_match() {
+        let router = router();
+        assert_eq!(route(&router, Method::Get, "/users"), Some(0));
+        assert_eq!(route(&router, Method::Post, "/users"), Some(1));
+        assert_eq!(route(&router, Method::Get, "/users/1"), Some(2));
+        assert_eq!(route(&router, Method::Delete, "/users/1"), Some(3));
+        assert_eq!(route(&router, Method::Get, "/files/a/b"), Some(4));
+        assert_eq!(route(&router, Method::Put, "/any/1"), Some(6));
+        assert_eq!(
+            route(&router, Method::Other("PURGE".into()), "/any/1"),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn not_found() {
+        let router = router();
+        assert!(matches!(
+            router.dispatch(&Method::Get, "/nope"),
+            Dispatch::NotFound
+        ));
+        assert!(matches!(
+            router.dispatch(&Method::Get, "/users/1/2"),
+            Dispatch::NotFound
+        ));
+        assert!(matches!(
+            Router::new().dispatch(&Method::Get, "/"),
+            Dispatch::NotFound
+        ));
+    }
+
+    #[test]
+    fn method_not_allowed() {
+        let router = router();
+        let allowed = |method, path| match router.dispatch(&method, path) {
+            Dispatch::MethodNotAllowed(allowed) => allowed.join(", "),
+            _ => panic!("expected 405 for {path}"),
+        };
+        assert_eq!(allowed(Method::Put, "/users"), "GET, POST, HEAD");
+        assert_eq!(allowed(Method::Patch, "/users/1"), "GET, DELETE, HEAD");
+        assert_eq!(allowed(Method::Get, "/health"), "HEAD");
+    }
+
+    #[test]
+    fn case_sensitive_methods() {
+        let router = router();
+        // Methods are case-sensitive, so `get` is a method of its own.
+        assert!(matches!(
+            router.dispatch(&Method::Other("get".into()), "/users"),
+            Dispatch::MethodNotAllowed(allowed) if allowed == ["GET", "POST", "HEAD"]
+        ));
+        assert!(matches!(
+            router.dispatch(&Method::Other("head".into()), "/users"),
+            Dispatch::MethodNotAllowed(_)
+        ));
+        // Standard methods spelled out as `Other` are the same method.
+        assert_eq!(
+            route(&router, Method::Other("GET".into()), "/users"),
+            Some(0)
+        );
+        assert_eq!(
+            route(&router, Method::Other("HEAD".into()), "/users"),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn head_falls_back_to_get() {
+        let router = router();
+        assert_eq!(route(&router, Method::Head, "/users"), Some(0));
+        assert_eq!(route(&router, Method::Head, "/users/1"), Some(2));
+        assert_eq!(route(&router, Method::Head, "/health"), Some(5));
+
+        // An explicit `HEAD` route takes precedence over an earlier `GET` one.
+        let router = Router::new()
+            .get("/x", |_, _| {})
+            .route(Method::Head, "/x", |_, _| {});
+        assert_eq!(route(&router, Method::Head, "/x"), Some(1));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%2Fc", false), "a b/c");
+        assert_eq!(percent_decode("%41%62", false), "Ab");
+        assert_eq!(percent_decode("a+b", false), "a+b");
+        assert_eq!(percent_decode("a+b%2B", true), "a b+");
+        assert_eq!(percent_decode("%e2%9c%93", false), "\u{2713}");
+    }
+
+    #[test]
+    fn invalid_percent_escapes() {
+        assert_eq!(percent_decode("%", false), "%");
+        assert_eq!(percent_decode("a%4", false), "a%4");
+        assert_eq!(percent_decode("%zz", false), "%zz");
+        assert_eq!(percent_decode("%%41", false), "%A");
+        assert_eq!(percent_decode("%+1", true), "% 1");
+        assert_eq!(percent_decode("%ff", false), "\u{fffd}");
+    }
+}
@@ -0,0 +1,299 @@
+//! Asynchronous wrappers around the stream resources of `wasi:io/streams`
+//! which integrate with the [`runtime`](crate::runtime) executor.
+//!
+//! With the `futures-io` feature enabled these wrappers implement
+//! `futures_io::AsyncRead` and `futures_io::AsyncWrite`, and with the `tokio`
+//! feature enabled they implement `tokio::io::AsyncRead` and
+//! `tokio::io::AsyncWrite`.
+//!
+//! Like the blocking impls of `std::io`, errors are converted like
+//! [`StreamError`], without recovering the error code of the stream's
+//! interface.
+
+use super::blocking::{copy_chunk, to_io_error, write_len};
+use crate::io::streams::{InputStream, OutputStream, StreamError};
+use crate::runtime::{self, WaitFor};
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// An [`InputStream`] which can be read from asynchronously.
+///
+/// Reads use the non-blocking [`InputStream::read`] and, whenever the host has
+/// no bytes available, wait on the stream's
+/// [`subscribe`](InputStream::subscribe) pollable through the
+/// [`runtime`](crate::runtime) reactor.
+///
+/// ```no_run
+/// use wasi::io::AsyncInputStream;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let mut stdin = AsyncInputStream::new(wasi::cli::stdin::get_stdin());
+/// let mut buf = [0; 1024];
+/// let n = stdin.read(&mut buf).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncInputStream {
+    // NB: this is declared before `stream` so the pollable it holds is
+    // dropped before the stream it was created from.
+    wait: Option<WaitFor>,
+    stream: InputStream,
+}
+
+impl AsyncInputStream {
+    /// Wraps `stream` for asynchronous reads.
+    pub fn new(stream: InputStream) -> AsyncInputStream {
+        AsyncInputStream { wait: None, stream }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &InputStream {
+        &self.stream
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> InputStream {
+        self.stream
+    }
+
+    /// Attempts to read bytes into `buf`, returning how many were read.
+    ///
+    /// Returns `Ok(0)` once the stream has been closed.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            if let Some(wait) = &mut self.wait {
+                ready!(Pin::new(wait).poll(cx));
+                self.wait = None;
+            }
+            let len = u64::try_from(buf.len()).unwrap_or(u64::MAX);
+            match self.stream.read(len) {
+                Ok(chunk) if chunk.is_empty() => {
+                    self.wait = Some(runtime::wait_for(self.stream.subscribe()));
+                }
+                Ok(chunk) => return Poll::Ready(copy_chunk(&chunk, buf)),
+                Err(StreamError::Closed) => return Poll::Ready(Ok(0)),
+                Err(e) => {
+                    return Poll::Ready(Err(to_io_error(e, io::ErrorKind::UnexpectedEof)));
+                }
+            }
+        }
+    }
+
+    /// Reads bytes into `buf`, returning how many were read.
+    ///
+    /// Returns `Ok(0)` once the stream has been closed.
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+}
+
+/// An [`OutputStream`] which can be written to asynchronously.
+///
+/// Writes respect the budget reported by [`OutputStream::check_write`] and
+/// wait on the stream's [`subscribe`](OutputStream::subscribe) pollable
+/// through the [`runtime`](crate::runtime) reactor whenever the host isn't
+/// ready to accept more bytes.
+pub struct AsyncOutputStream {
+    // NB: this is declared before `stream` so the pollable it holds is
+    // dropped before the stream it was created from.
+    wait: Option<WaitFor>,
+    flushing: bool,
+    stream: OutputStream,
+}
+
+impl AsyncOutputStream {
+    /// Wraps `stream` for asynchronous writes.
+    pub fn new(stream: OutputStream) -> AsyncOutputStream {
+        AsyncOutputStream {
+            wait: None,
+            flushing: false,
+            stream,
+        }
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &OutputStream {
+        &self.stream
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> OutputStream {
+        self.stream
+    }
+
+    /// Waits until [`OutputStream::check_write`] reports a non-zero budget,
+    /// returning that budget.
+    fn poll_check_write(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        loop {
+            if let Some(wait) = &mut self.wait {
+                ready!(Pin::new(wait).poll(cx));
+                self.wait = None;
+            }
+            match self.stream.check_write() {
+                Ok(0) => self.wait = Some(runtime::wait_for(self.stream.subscribe())),
+                Ok(n) => return Poll::Ready(Ok(n)),
+                Err(e) => return Poll::Ready(Err(to_io_error(e, io::ErrorKind::BrokenPipe))),
+            }
+        }
+    }
+
+    /// Attempts to write bytes from `buf`, returning how many were written.
+    ///
+    /// At most as many bytes as permitted by [`OutputStream::check_write`]
+    /// are written.
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let permit = ready!(self.poll_check_write(cx))?;
+        let n = write_len(permit, buf);
+        self.stream
+            .write(&buf[..n])
+            .map_err(|e| to_io_error(e, io::ErrorKind::BrokenPipe))?;
+        Poll::Ready(Ok(n))
+    }
+
+    /// Attempts to flush all bytes written so far.
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.flushing {
+            self.stream
+                .flush()
+                .map_err(|e| to_io_error(e, io::ErrorKind::BrokenPipe))?;
+            self.flushing = true;
+        }
+        // The stream reports a write budget of zero until the flush has
+        // completed.
+        let result = ready!(self.poll_check_write(cx));
+        self.flushing = false;
+        Poll::Ready(result.map(drop))
+    }
+
+    /// Writes bytes from `buf`, returning how many were written.
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    /// Writes all of `buf` to the stream.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes all bytes written so far.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        poll_fn(|cx| self.poll_flush(cx)).await
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for AsyncInputStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncWrite for AsyncOutputStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for AsyncInputStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = ready!(self.get_mut().poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncOutputStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks() {
+        let mut buf = [0; 4];
+        assert_eq!(copy_chunk(b"ab", &mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"ab\0\0");
+        assert_eq!(copy_chunk(b"wxyz", &mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"wxyz");
+        assert_eq!(copy_chunk(b"", &mut buf).unwrap(), 0);
+        // A host returning more than requested is an error rather than a
+        // panic or lost bytes.
+        let err = copy_chunk(b"vwxyz", &mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(&buf, b"wxyz");
+    }
+
+    #[test]
+    fn write_budgets() {
+        assert_eq!(write_len(1, b"abc"), 1);
+        assert_eq!(write_len(3, b"abc"), 3);
+        assert_eq!(write_len(4096, b"abc"), 3);
+        assert_eq!(write_len(u64::MAX, b"abc"), 3);
+        assert_eq!(write_len(4096, b""), 0);
+    }
+
+    #[test]
+    fn stream_errors() {
+        let closed = to_io_error(StreamError::Closed, io::ErrorKind::UnexpectedEof);
+        assert_eq!(closed.kind(), io::ErrorKind::UnexpectedEof);
+        let closed = to_io_error(StreamError::Closed, io::ErrorKind::BrokenPipe);
+        assert_eq!(closed.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(
+            io::Error::from(StreamError::Closed).kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Implementations of the blocking `std::io` traits for the stream resources
+//! of `wasi:io/streams`.
+//!
+//! Reads are performed with [`InputStream::blocking_read`] and writes respect
+//! the budget reported by [`OutputStream::check_write`], blocking on the
+//! stream's [`Pollable`](crate::io::poll::Pollable) until the host is ready
+//! to accept more bytes. This means that the streams returned from functions
+//! such as [`get_stdin`](crate::cli::stdin::get_stdin) can be wrapped in a
+//! [`std::io::BufReader`] or passed to anything taking `impl Read` or
+//! `impl Write`.
+//!
+//! Errors are converted without looking up the error code of the stream's
+//! interface, see `From<IoError> for io::Error`.
+
+use crate::io::error::Error as IoError;
+use crate::io::streams::{InputStream, OutputStream, StreamError};
+use std::io;
+
+/// The number of bytes requested from the host per call to `splice` in
+/// [`copy`].
+const COPY_CHUNK: u64 = 64 * 1024;
+
+impl io::Read for InputStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut &*self, buf)
+    }
+}
+
+/// Reads with [`InputStream::blocking_read`].
+///
+/// Errors are converted like [`StreamError`], without recovering the error
+/// code of the stream's interface. Files and HTTP bodies are better read
+/// through [`crate::fs::File`] and [`crate::http::body::BodyReader`], which
+/// do recover it.
+impl io::Read for &InputStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = u64::try_from(buf.len()).unwrap_or(u64::MAX);
+        match self.blocking_read(len) {
+            Ok(chunk) => copy_chunk(&chunk, buf),
+            Err(StreamError::Closed) => Ok(0),
+            Err(e) => Err(to_io_error(e, io::ErrorKind::UnexpectedEof)),
+        }
+    }
+}
+
+impl io::Write for OutputStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut &*self, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut &*self)
+    }
+}
+
+/// Writes within the budget of [`OutputStream::check_write`].
+///
+/// Errors are converted like [`StreamError`], without recovering the error
+/// code of the stream's interface. Files and HTTP bodies are better written
+/// through [`crate::fs::File`] and [`crate::http::body::BodyWriter`], which
+/// do recover it.
+impl io::Write for &OutputStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let permit = loop {
+            match self.check_write() {
+                Ok(0) => self.subscribe().block(),
+                Ok(n) => break n,
+                Err(e) => return Err(to_io_error(e, io::ErrorKind::BrokenPipe)),
+            }
+        };
+        let n = write_len(permit, buf);
+        OutputStream::write(self, &buf[..n])
+            .map_err(|e| to_io_error(e, io::ErrorKind::BrokenPipe))?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.blocking_flush()
+            .map_err(|e| to_io_error(e, io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// Copies the entire contents of `input` into `output`, returning the number
+/// of bytes transferred.
+///
+/// This is the `wasi:io` equivalent of [`std::io::copy`] and is built on
+/// [`OutputStream::blocking_splice`], which lets the host move bytes between
+/// the two streams without round-tripping them through guest memory. Copying
+/// stops once `input` reaches the end of its stream, after which `output` is
+/// flushed.
+///
+/// An error of kind [`BrokenPipe`](io::ErrorKind::BrokenPipe) is returned if
+/// `output` is closed before `input` is exhausted. Other errors are converted
+/// like [`StreamError`], without recovering the error code of the streams'
+/// interface.
+///
+/// ```no_run
+/// let stdin = wasi::cli::stdin::get_stdin();
+/// let stdout = wasi::cli::stdout::get_stdout();
+/// wasi::io::copy(&stdin, &stdout)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn copy(input: &InputStream, output: &OutputStream) -> io::Result<u64> {
+    let mut copied = 0;
+    loop {
+        match output.blocking_splice(input, COPY_CHUNK) {
+            Ok(n) => copied += n,
+            Err(StreamError::Closed) => {
+                // `splice` doesn't say which of the two streams was closed, so
+                // check the output: if it's still accepting writes then the
+                // input has simply reached its end.
+                return match output.check_write() {
+                    Ok(_) => {
+                        io::Write::flush(&mut &*output)?;
+                        Ok(copied)
+                    }
+                    Err(e) => Err(to_io_error(e, io::ErrorKind::BrokenPipe)),
+                };
+            }
+            Err(e) => return Err(to_io_error(e, io::ErrorKind::Other)),
+        }
+    }
+}
+
+/// Copies `chunk` read from a stream into `buf`, returning its length.
+///
+/// The host is not allowed to return more bytes than requested, but isn't
+/// trusted to avoid panicking in `copy_from_slice` or dropping bytes.
+pub(crate) fn copy_chunk(chunk: &[u8], buf: &mut [u8]) -> io::Result<usize> {
+    let dest = buf
+        .get_mut(..chunk.len())
+        .ok_or_else(|| io::Error::other("more bytes read than requested"))?;
+    dest.copy_from_slice(chunk);
+    Ok(chunk.len())
+}
+
+/// Returns how many bytes of `buf` to write within the budget `permit` of
+/// [`OutputStream::check_write`].
+pub(crate) fn write_len(permit: u64, buf: &[u8]) -> usize {
+    usize::try_from(permit).unwrap_or(usize::MAX).min(buf.len())
+}
+
+/// Converts a [`StreamError`] into an [`io::Error`], using `closed` as the
+/// kind of error for [`StreamError::Closed`].
+pub(crate) fn to_io_error(err: StreamError, closed: io::ErrorKind) -> io::Error {
+    match err {
+        StreamError::Closed => io::Error::from(closed),
+        StreamError::LastOperationFailed(e) => io::Error::other(e.to_debug_string()),
+    }
+}
+
+/// Converts an [`IoError`] into an [`io::Error`] of kind
+/// [`io::ErrorKind::Other`] carrying the error's debug string.
+///
+/// This deliberately doesn't look up the error code behind the error with
+/// [`filesystem_error_code`](crate::filesystem::types::filesystem_error_code)
+/// or [`http_error_code`](crate::http::types::http_error_code), since doing
+/// so would make every component using this conversion import both
+/// `wasi:filesystem` and `wasi:http`, which worlds such as `wasi:http/proxy`
+/// don't include. Errors of files' streams are converted with their
+/// [`ErrorCode`](crate::filesystem::types::ErrorCode) and its
+/// [`io::ErrorKind`] by [`crate::fs::stream_error`], and errors of HTTP
+/// bodies' streams with theirs by [`crate::http::body::stream_error`].
+impl From<IoError> for io::Error {
+    fn from(err: IoError) -> io::Error {
+        io::Error::other(err.to_debug_string())
+    }
+}
+
+/// Converts a [`StreamError`] into an [`io::Error`], with
+/// [`StreamError::Closed`] mapping to [`io::ErrorKind::BrokenPipe`] and
+/// [`StreamError::LastOperationFailed`] converted like an [`IoError`],
+/// without looking up its error code.
+impl From<StreamError> for io::Error {
+    fn from(err: StreamError) -> io::Error {
+        match err {
+            StreamError::Closed => io::Error::from(io::ErrorKind::BrokenPipe),
+            StreamError::LastOperationFailed(e) => e.into(),
+        }
+    }
+}
@@ -234,9 +234,37 @@ pub mod cli {
     }
 }
 
+// Expand the `io` module with implementations of the `std::io` traits for its
+// stream resources along with asynchronous wrappers for them.
+pub mod io {
+    pub use super::bindings::wasi::io::*;
+
+    #[cfg(feature = "std")]
+    mod async_io;
+    #[cfg(feature = "std")]
+    pub(crate) mod blocking;
+    #[cfg(feature = "std")]
+    pub use self::async_io::{AsyncInputStream, AsyncOutputStream};
+    #[cfg(feature = "std")]
+    pub use self::blocking::copy;
+}
+
 pub mod http {
     pub use super::bindings::wasi::http::*;
 
+    #[cfg(feature = "std")]
+    pub mod body;
+    #[cfg(feature = "std")]
+    pub mod client;
+    #[cfg(feature = "http")]
+    pub mod compat;
+    #[cfg(feature = "std")]
+    pub mod headers;
+    #[cfg(feature = "std")]
+    pub mod response;
+    #[cfg(feature = "std")]
+    pub mod router;
+
     pub mod proxy {
         /// Generate an exported instance of the `wasi:http/proxy` world.
         ///
@@ -269,6 +297,31 @@ pub mod http {
     }
 }
 
+// Expand the `filesystem` module with conversions from its error codes to
+// `std::io::Error`.
+pub mod filesystem {
+    pub use super::bindings::wasi::filesystem::*;
+
+    mod error;
+}
+
+// Expand the `sockets` module with conversions between its address types and
+// those of `core::net`, along with conversions from its error codes to
+// `std::io::Error`.
+pub mod sockets {
+    pub use super::bindings::wasi::sockets::*;
+
+    mod addr;
+    mod error;
+}
+
+#[cfg(feature = "std")]
+pub mod fs;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "std")]
+pub mod runtime;
+
 pub mod exports {
     // This is required by the `export!` macros of this crate which assume that
     // the types it's referring to show up as `exports::wasi::...`.
@@ -0,0 +1,392 @@
+use crate::runtime;
+use crate::sockets::instance_network::instance_network;
+use crate::sockets::ip_name_lookup::{resolve_addresses, ResolveAddressStream};
+use crate::sockets::network::{ErrorCode, IpAddress};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::string::String;
+use std::vec::{self, Vec};
+use std::{option, slice};
+
+/// Resolves `host`, a string of the form `host:port`, to the socket addresses
+/// it refers to, blocking until the resolver has returned every address.
+///
+/// Addresses are returned in connection order preference. If the host part
+/// is already an IP address then it is returned without making any external
+/// requests.
+///
+/// ```no_run
+/// for addr in wasi::net::lookup_host("example.com:443")? {
+///     println!("{addr}");
+/// }
+/// # Ok::<(), wasi::net::LookupError>(())
+/// ```
+pub fn lookup_host(host: &str) -> Result<LookupHost, LookupError> {
+    let (name, port) = match split_host_port(host)? {
+        Ok(addr) => return Ok(LookupHost::single(addr)),
+        Err(name_and_port) => name_and_port,
+    };
+    resolve(name, port)
+}
+
+/// Asynchronous version of [`lookup_host`] which waits on the resolver
+/// through the [`runtime`](crate::runtime) reactor.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), wasi::net::LookupError> {
+/// let addrs = wasi::net::lookup_host_async("example.com:443").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn lookup_host_async(host: &str) -> Result<LookupHost, LookupError> {
+    let (name, port) = match split_host_port(host)? {
+        Ok(addr) => return Ok(LookupHost::single(addr)),
+        Err(name_and_port) => name_and_port,
+    };
+    let stream = start_lookup(name)?;
+    let mut addrs = Vec::new();
+    loop {
+        match stream.resolve_next_address() {
+            Ok(Some(ip)) => addrs.push(to_socket_addr(ip, port)),
+            Ok(None) => break,
+            Err(ErrorCode::WouldBlock) => runtime::wait_for(stream.subscribe()).await,
+            Err(code) => return Err(code.into()),
+        }
+    }
+    Ok(LookupHost {
+        addrs: addrs.into_iter(),
+    })
+}
+
+/// Parses `host` as either a socket address or a name and a port.
+///
+/// IPv6 addresses must be enclosed in brackets, like in [`SocketAddr`], so
+/// names containing `:`, `[` or `]` are rejected.
+fn split_host_port(host: &str) -> Result<Result<SocketAddr, (&str, u16)>, LookupError> {
+    if let Ok(addr) = host.parse() {
+        return Ok(Ok(addr));
+    }
+    let (name, port) = host.rsplit_once(':').ok_or(LookupError::InvalidInput)?;
+    if name.is_empty() || name.contains([':', '[', ']']) {
+        return Err(LookupError::InvalidInput);
+    }
+    let port = port.parse().map_err(|_| LookupError::InvalidInput)?;
+    Ok(Err((name, port)))
+}
+
+/// Resolves `name` to the addresses it refers to, blocking until the resolver
+/// has returned every address.
+fn resolve(name: &str, port: u16) -> Result<LookupHost, LookupError> {
+    if let Ok(ip) = name.parse() {
+        return Ok(LookupHost::single(SocketAddr::new(ip, port)));
+    }
+    let stream = start_lookup(name)?;
+    let mut pollable = None;
+    let mut addrs = Vec::new();
+    loop {
+        match stream.resolve_next_address() {
+            Ok(Some(ip)) => addrs.push(to_socket_addr(ip, port)),
+            Ok(None) => break,
+            Err(ErrorCode::WouldBlock) => {
+                pollable.get_or_insert_with(|| stream.subscribe()).block()
+            }
+            Err(code) => return Err(code.into()),
+        }
+    }
+    Ok(LookupHost {
+        addrs: addrs.into_iter(),
+    })
+}
+
+fn start_lookup(name: &str) -> Result<ResolveAddressStream, LookupError> {
+    Ok(resolve_addresses(&instance_network(), name)?)
+}
+
+fn to_socket_addr(ip: IpAddress, port: u16) -> SocketAddr {
+    SocketAddr::new(IpAddr::from(ip), port)
+}
+
+/// An iterator over the addresses returned by [`lookup_host`].
+#[derive(Debug)]
+pub struct LookupHost {
+    addrs: vec::IntoIter<SocketAddr>,
+}
+
+impl LookupHost {
+    fn single(addr: SocketAddr) -> LookupHost {
+        LookupHost {
+            addrs: std::vec![addr].into_iter(),
+        }
+    }
+}
+
+impl Iterator for LookupHost {
+    type Item = SocketAddr;
+
+    fn next(&mut self) -> Option<SocketAddr> {
+        self.addrs.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.addrs.size_hint()
+    }
+}
+
+/// Errors returned by [`lookup_host`].
+#[derive(Debug)]
+pub enum LookupError {
+    /// The input isn't of the form `host:port` or the host isn't a
+    /// syntactically valid domain name or IP address.
+    InvalidInput,
+    /// The name doesn't exist or has no suitable addresses.
+    NameUnresolvable,
+    /// A temporary failure occurred in the resolver, and retrying may
+    /// succeed.
+    TemporaryResolverFailure,
+    /// A permanent failure occurred in the resolver.
+    PermanentResolverFailure,
+    /// Any other error reported by the host.
+    Other(ErrorCode),
+}
+
+impl From<ErrorCode> for LookupError {
+    fn from(code: ErrorCode) -> LookupError {
+        match code {
+            ErrorCode::InvalidArgument => LookupError::InvalidInput,
+            ErrorCode::NameUnresolvable => LookupError::NameUnresolvable,
+            ErrorCode::TemporaryResolverFailure => LookupError::TemporaryResolverFailure,
+            ErrorCode::PermanentResolverFailure => LookupError::PermanentResolverFailure,
+            code => LookupError::Other(code),
+        }
+    }
+}
+
+impl From<LookupError> for io::Error {
+    fn from(e: LookupError) -> io::Error {
+        match e {
+            LookupError::InvalidInput => io::Error::new(io::ErrorKind::InvalidInput, e),
+            LookupError::NameUnresolvable => io::Error::new(io::ErrorKind::NotFound, e),
+            LookupError::Other(code) => code.into(),
+            e => io::Error::other(e),
+        }
+    }
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::InvalidInput => f.write_str("invalid host name or socket address"),
+            LookupError::NameUnresolvable => f.write_str("name could not be resolved"),
+            LookupError::TemporaryResolverFailure => {
+                f.write_str("temporary failure in name resolution")
+            }
+            LookupError::PermanentResolverFailure => {
+                f.write_str("permanent failure in name resolution")
+            }
+            LookupError::Other(code) => code.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LookupError::Other(code) => Some(code),
+            _ => None,
+        }
+    }
+}
+
+/// A value which can be converted to one or more socket addresses, similar
+/// to [`std::net::ToSocketAddrs`].
+///
+/// Strings are resolved with [`lookup_host`] rather than through the
+/// standard library so they work with `wasi:sockets`.
+pub trait ToSocketAddrs {
+    /// The iterator over the socket addresses of this value.
+    type Iter: Iterator<Item = SocketAddr>;
+
+    /// Converts this value to an iterator of socket addresses.
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter>;
+}
+
+impl ToSocketAddrs for SocketAddr {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(Some(*self).into_iter())
+    }
+}
+
+impl ToSocketAddrs for SocketAddrV4 {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::V4(*self).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for SocketAddrV6 {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::V6(*self).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (IpAddr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(self.0, self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (Ipv4Addr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(IpAddr::V4(self.0), self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (Ipv6Addr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(IpAddr::V6(self.0), self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (&str, u16) {
+    type Iter = LookupHost;
+
+    fn to_socket_addrs(&self) -> io::Result<LookupHost> {
+        let (host, port) = *self;
+        Ok(resolve(host, port)?)
+    }
+}
+
+impl ToSocketAddrs for (String, u16) {
+    type Iter = LookupHost;
+
+    fn to_socket_addrs(&self) -> io::Result<LookupHost> {
+        (&*self.0, self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for str {
+    type Iter = LookupHost;
+
+    fn to_socket_addrs(&self) -> io::Result<LookupHost> {
+        Ok(lookup_host(self)?)
+    }
+}
+
+impl ToSocketAddrs for String {
+    type Iter = LookupHost;
+
+    fn to_socket_addrs(&self) -> io::Result<LookupHost> {
+        Ok(lookup_host(self)?)
+    }
+}
+
+impl<'a> ToSocketAddrs for &'a [SocketAddr] {
+    type Iter = std::iter::Copied<slice::Iter<'a, SocketAddr>>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(self.iter().copied())
+    }
+}
+
+impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {
+    type Iter = T::Iter;
+
+    fn to_socket_addrs(&self) -> io::Result<T::Iter> {
+        (**self).to_socket_addrs()
+    }
+}
+
+/// Calls `f` with each address of `addr` until it succeeds, returning the
+/// last error if every attempt fails.
+pub(super) fn each_addr<A: ToSocketAddrs, T>(
+    addr: A,
+    mut f: impl FnMut(SocketAddr) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match f(addr) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(host: &str) -> SocketAddr {
+        match split_host_port(host) {
+            Ok(Ok(addr)) => addr,
+            other => panic!("expected an address for {host}, got {other:?}"),
+        }
+    }
+
+    fn name(host: &str) -> (&str, u16) {
+        match split_host_port(host) {
+            Ok(Err(name_and_port)) => name_and_port,
+            other => panic!("expected a name for {host}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn socket_addresses() {
+        assert_eq!(addr("127.0.0.1:80"), SocketAddr::from(([127, 0, 0, 1], 80)));
+        assert_eq!(
+            addr("[::1]:443"),
+            SocketAddr::from((Ipv6Addr::LOCALHOST, 443))
+        );
+        assert_eq!(
+            addr("[fe80::1%3]:8080"),
+            SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 8080, 0, 3))
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(name("example.com:443"), ("example.com", 443));
+        assert_eq!(name("localhost:0"), ("localhost", 0));
+        assert_eq!(name("a.b.c:65535"), ("a.b.c", 65535));
+    }
+
+    #[test]
+    fn invalid_input() {
+        for host in [
+            "",
+            "example.com",
+            "example.com:",
+            "example.com:http",
+            "example.com:65536",
+            ":80",
+            "::1",
+            "::1:80",
+            "[::1]",
+            "[::1]:",
+            "[example.com]:80",
+            "1.2.3.4",
+        ] {
+            assert!(
+                matches!(split_host_port(host), Err(LookupError::InvalidInput)),
+                "{host}"
+            );
+        }
+    }
+}
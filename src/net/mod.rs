@@ -0,0 +1,109 @@
+//! Blocking networking primitives in the style of [`std::net`], built on
+//! [`wasi:sockets`].
+//!
+//! [`wasi:sockets`]: https://github.com/WebAssembly/wasi-sockets
+//!
+//! The sockets of `wasi:sockets` are non-blocking and split operations such
+//! as connecting into a `start-*` and `finish-*` pair. The types in this module
+//! hide that protocol by blocking on the socket's pollable until each
+//! operation completes, using the network returned by
+//! [`instance_network`](crate::sockets::instance_network::instance_network).
+//!
+//! Host names are resolved with [`lookup_host`] through
+//! [`wasi:sockets/ip-name-lookup`](crate::sockets::ip_name_lookup), and every
+//! function taking an address accepts anything implementing
+//! [`ToSocketAddrs`], including `"host:port"` strings.
+//!
+//! Values that `wasi:sockets` always rejects are checked up front and fail
+//! with an error of kind [`InvalidInput`](std::io::ErrorKind::InvalidInput):
+//! remote addresses with an unspecified IP address or port 0, and socket
+//! options such as buffer sizes, hop limits and keep-alive times set to 0.
+
+use crate::io::poll::Pollable;
+use crate::sockets::network::ErrorCode;
+use std::io;
+use std::net::SocketAddr;
+
+mod lookup;
+mod tcp;
+mod udp;
+
+pub use self::lookup::{lookup_host, lookup_host_async, LookupError, LookupHost, ToSocketAddrs};
+pub use self::tcp::{Incoming, TcpListener, TcpStream};
+pub use self::udp::UdpSocket;
+
+/// Retries `op` until it stops failing with [`ErrorCode::WouldBlock`],
+/// blocking on `subscribe()` in between attempts.
+fn block_until_done<T>(
+    subscribe: impl Fn() -> Pollable,
+    mut op: impl FnMut() -> Result<T, ErrorCode>,
+) -> Result<T, ErrorCode> {
+    let mut pollable = None;
+    loop {
+        match op() {
+            Err(ErrorCode::WouldBlock) => pollable.get_or_insert_with(&subscribe).block(),
+            result => return result,
+        }
+    }
+}
+
+/// Checks that `addr` can be connected or sent to, which `wasi:sockets`
+/// rejects with `invalid-argument` if its IP address is unspecified, as in
+/// `0.0.0.0` or `::`, or its port is 0.
+fn remote_addr(addr: SocketAddr) -> io::Result<SocketAddr> {
+    if addr.ip().is_unspecified() || addr.port() == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "remote address or port is unspecified",
+        ));
+    }
+    Ok(addr)
+}
+
+/// Checks that the value of a socket option is non-zero, which `wasi:sockets`
+/// requires of every hop limit, buffer size, count and duration.
+fn nonzero<T: Copy + Into<u64>>(value: T) -> io::Result<T> {
+    if value.into() == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "socket option must be non-zero",
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_addrs() {
+        for addr in ["127.0.0.1:80", "[::1]:443", "10.0.0.1:65535"] {
+            let addr = addr.parse().unwrap();
+            assert_eq!(remote_addr(addr).unwrap(), addr);
+        }
+        for addr in ["0.0.0.0:80", "[::]:80", "127.0.0.1:0", "[::1]:0"] {
+            let err = remote_addr(addr.parse().unwrap()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{addr}");
+        }
+    }
+
+    #[test]
+    fn nonzero_options() {
+        assert_eq!(nonzero(1u8).unwrap(), 1);
+        assert_eq!(nonzero(u32::MAX).unwrap(), u32::MAX);
+        assert_eq!(nonzero(4096u64).unwrap(), 4096);
+        assert_eq!(
+            nonzero(0u8).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            nonzero(0u32).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            nonzero(0u64).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
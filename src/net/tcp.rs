@@ -0,0 +1,349 @@
+use super::lookup::each_addr;
+use super::{block_until_done, nonzero, remote_addr, ToSocketAddrs};
+use crate::io::streams::{InputStream, OutputStream};
+use crate::sockets::instance_network::instance_network;
+use crate::sockets::tcp::{ShutdownType, TcpSocket};
+use crate::sockets::tcp_create_socket::create_tcp_socket;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+use std::time::Duration;
+
+/// A TCP stream between a local and a remote socket, similar to
+/// [`std::net::TcpStream`].
+///
+/// ```no_run
+/// use std::io::{Read, Write};
+/// use wasi::net::TcpStream;
+///
+/// let mut stream = TcpStream::connect("127.0.0.1:8080")?;
+/// stream.write_all(b"ping")?;
+/// let mut buf = [0; 4];
+/// stream.read_exact(&mut buf)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct TcpStream {
+    // NB: the streams are children of the socket so they're declared first to
+    // be dropped before it.
+    input: InputStream,
+    output: OutputStream,
+    socket: TcpSocket,
+}
+
+impl TcpStream {
+    /// Opens a TCP connection to `addr`, blocking until it is established.
+    ///
+    /// If `addr` resolves to multiple addresses then each one is tried in
+    /// turn until a connection succeeds, returning the error of the last
+    /// attempt if none do.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        each_addr(addr, TcpStream::connect_addr)
+    }
+
+    fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let addr = remote_addr(addr)?;
+        let socket = create_tcp_socket(addr.into())?;
+        socket.start_connect(&instance_network(), addr.into())?;
+        let (input, output) = block_until_done(|| socket.subscribe(), || socket.finish_connect())?;
+        Ok(TcpStream {
+            input,
+            output,
+            socket,
+        })
+    }
+
+    /// Returns the address of the remote peer of this connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .remote_address()
+            .map(SocketAddr::from)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the local address of this connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .local_address()
+            .map(SocketAddr::from)
+            .map_err(io::Error::from)
+    }
+
+    /// Shuts down the read half, write half, or both halves of this
+    /// connection.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.socket
+            .shutdown(shutdown_type(how))
+            .map_err(io::Error::from)
+    }
+
+    /// Enables or disables `SO_KEEPALIVE`.
+    pub fn set_keep_alive(&self, enabled: bool) -> io::Result<()> {
+        self.socket
+            .set_keep_alive_enabled(enabled)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns whether `SO_KEEPALIVE` is enabled.
+    pub fn keep_alive(&self) -> io::Result<bool> {
+        self.socket.keep_alive_enabled().map_err(io::Error::from)
+    }
+
+    /// Sets the time a connection must be idle before keep-alive packets are
+    /// sent, similar to `TCP_KEEPIDLE`.
+    pub fn set_keep_alive_idle_time(&self, time: Duration) -> io::Result<()> {
+        self.socket
+            .set_keep_alive_idle_time(nanos(time)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the time a connection must be idle before keep-alive packets
+    /// are sent.
+    pub fn keep_alive_idle_time(&self) -> io::Result<Duration> {
+        self.socket
+            .keep_alive_idle_time()
+            .map(Duration::from_nanos)
+            .map_err(io::Error::from)
+    }
+
+    /// Sets the time between keep-alive packets, similar to `TCP_KEEPINTVL`.
+    pub fn set_keep_alive_interval(&self, interval: Duration) -> io::Result<()> {
+        self.socket
+            .set_keep_alive_interval(nanos(interval)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the time between keep-alive packets.
+    pub fn keep_alive_interval(&self) -> io::Result<Duration> {
+        self.socket
+            .keep_alive_interval()
+            .map(Duration::from_nanos)
+            .map_err(io::Error::from)
+    }
+
+    /// Sets the number of unacknowledged keep-alive packets after which the
+    /// connection is dropped, similar to `TCP_KEEPCNT`.
+    pub fn set_keep_alive_count(&self, count: u32) -> io::Result<()> {
+        self.socket
+            .set_keep_alive_count(nonzero(count)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the number of unacknowledged keep-alive packets after which
+    /// the connection is dropped.
+    pub fn keep_alive_count(&self) -> io::Result<u32> {
+        self.socket.keep_alive_count().map_err(io::Error::from)
+    }
+
+    /// Sets the hop limit of outgoing packets, similar to `IP_TTL` and
+    /// `IPV6_UNICAST_HOPS`.
+    pub fn set_ttl(&self, ttl: u8) -> io::Result<()> {
+        self.socket
+            .set_hop_limit(nonzero(ttl)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the hop limit of outgoing packets.
+    pub fn ttl(&self) -> io::Result<u8> {
+        self.socket.hop_limit().map_err(io::Error::from)
+    }
+
+    /// Sets the size of the kernel's receive buffer, similar to `SO_RCVBUF`.
+    pub fn set_receive_buffer_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_receive_buffer_size(nonzero(size)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the size of the kernel's receive buffer.
+    pub fn receive_buffer_size(&self) -> io::Result<u64> {
+        self.socket.receive_buffer_size().map_err(io::Error::from)
+    }
+
+    /// Sets the size of the kernel's send buffer, similar to `SO_SNDBUF`.
+    pub fn set_send_buffer_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_send_buffer_size(nonzero(size)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the size of the kernel's send buffer.
+    pub fn send_buffer_size(&self) -> io::Result<u64> {
+        self.socket.send_buffer_size().map_err(io::Error::from)
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &TcpSocket {
+        &self.socket
+    }
+
+    /// Returns the stream for reading from this connection.
+    pub fn input(&self) -> &InputStream {
+        &self.input
+    }
+
+    /// Returns the stream for writing to this connection.
+    pub fn output(&self) -> &OutputStream {
+        &self.output
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+}
+
+impl Read for &TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &self.input, buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut &*self)
+    }
+}
+
+impl Write for &TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &self.output, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut &self.output)
+    }
+}
+
+/// A TCP socket listening for connections, similar to
+/// [`std::net::TcpListener`].
+///
+/// ```no_run
+/// use std::io::Write;
+/// use wasi::net::TcpListener;
+///
+/// let listener = TcpListener::bind("0.0.0.0:8080")?;
+/// for stream in listener.incoming() {
+///     stream?.write_all(b"hello\n")?;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct TcpListener {
+    socket: TcpSocket,
+}
+
+impl TcpListener {
+    /// Creates a socket bound to `addr` and starts listening on it.
+    ///
+    /// If `addr` resolves to multiple addresses then each one is tried in
+    /// turn until binding succeeds.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        each_addr(addr, TcpListener::bind_addr)
+    }
+
+    fn bind_addr(addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = create_tcp_socket(addr.into())?;
+        socket.start_bind(&instance_network(), addr.into())?;
+        block_until_done(|| socket.subscribe(), || socket.finish_bind())?;
+        socket.start_listen()?;
+        block_until_done(|| socket.subscribe(), || socket.finish_listen())?;
+        Ok(TcpListener { socket })
+    }
+
+    /// Blocks until a new connection is accepted, returning it along with the
+    /// address of the remote peer.
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (socket, input, output) =
+            block_until_done(|| self.socket.subscribe(), || self.socket.accept())?;
+        let stream = TcpStream {
+            input,
+            output,
+            socket,
+        };
+        let addr = stream.peer_addr()?;
+        Ok((stream, addr))
+    }
+
+    /// Returns an iterator over the connections accepted by this listener.
+    ///
+    /// The iterator never returns `None`.
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self }
+    }
+
+    /// Returns the local address this listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .local_address()
+            .map(SocketAddr::from)
+            .map_err(io::Error::from)
+    }
+
+    /// Sets the maximum number of pending connections, similar to the
+    /// `backlog` argument of `listen` in POSIX.
+    pub fn set_listen_backlog_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_listen_backlog_size(nonzero(size)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &TcpSocket {
+        &self.socket
+    }
+}
+
+/// An iterator over the connections of a [`TcpListener`], returned from
+/// [`TcpListener::incoming`].
+pub struct Incoming<'a> {
+    listener: &'a TcpListener,
+}
+
+impl Iterator for Incoming<'_> {
+    type Item = io::Result<TcpStream>;
+
+    fn next(&mut self) -> Option<io::Result<TcpStream>> {
+        Some(self.listener.accept().map(|(stream, _)| stream))
+    }
+}
+
+fn shutdown_type(how: Shutdown) -> ShutdownType {
+    match how {
+        Shutdown::Read => ShutdownType::Receive,
+        Shutdown::Write => ShutdownType::Send,
+        Shutdown::Both => ShutdownType::Both,
+    }
+}
+
+/// Converts `duration` to the nanoseconds of a keep-alive option, saturating
+/// at `u64::MAX` and rejecting a zero duration.
+fn nanos(duration: Duration) -> io::Result<u64> {
+    nonzero(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_types() {
+        assert!(matches!(
+            shutdown_type(Shutdown::Read),
+            ShutdownType::Receive
+        ));
+        assert!(matches!(shutdown_type(Shutdown::Write), ShutdownType::Send));
+        assert!(matches!(shutdown_type(Shutdown::Both), ShutdownType::Both));
+    }
+
+    #[test]
+    fn keep_alive_durations() {
+        assert_eq!(nanos(Duration::from_nanos(1)).unwrap(), 1);
+        assert_eq!(nanos(Duration::from_secs(7200)).unwrap(), 7_200_000_000_000);
+        assert_eq!(nanos(Duration::MAX).unwrap(), u64::MAX);
+        let err = nanos(Duration::ZERO).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,288 @@
+use super::lookup::each_addr;
+use super::{block_until_done, nonzero, remote_addr, ToSocketAddrs};
+use crate::sockets::instance_network::instance_network;
+use crate::sockets::udp::{self, IncomingDatagramStream, OutgoingDatagram, OutgoingDatagramStream};
+use crate::sockets::udp_create_socket::create_udp_socket;
+use std::cell::{Ref, RefCell};
+use std::io;
+use std::net::SocketAddr;
+use std::vec::Vec;
+
+/// A UDP socket, similar to [`std::net::UdpSocket`].
+///
+/// Datagrams are sent and received through the streams returned by
+/// [`udp::UdpSocket::stream`], blocking on their pollables until the host is
+/// ready to send or has received a datagram.
+///
+/// ```no_run
+/// use wasi::net::UdpSocket;
+///
+/// let socket = UdpSocket::bind("0.0.0.0:34254")?;
+/// let mut buf = [0; 1500];
+/// let (n, peer) = socket.recv_from(&mut buf)?;
+/// socket.send_to(&buf[..n], peer)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct UdpSocket {
+    // NB: the streams are children of the socket so they're declared first to
+    // be dropped before it.
+    streams: RefCell<Option<Streams>>,
+    socket: udp::UdpSocket,
+}
+
+struct Streams {
+    incoming: IncomingDatagramStream,
+    outgoing: OutgoingDatagramStream,
+}
+
+impl UdpSocket {
+    /// Creates a socket bound to `addr`.
+    ///
+    /// If `addr` resolves to multiple addresses then each one is tried in
+    /// turn until binding succeeds.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
+        each_addr(addr, UdpSocket::bind_addr)
+    }
+
+    fn bind_addr(addr: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = create_udp_socket(addr.into())?;
+        socket.start_bind(&instance_network(), addr.into())?;
+        block_until_done(|| socket.subscribe(), || socket.finish_bind())?;
+        let (incoming, outgoing) = socket.stream(None)?;
+        Ok(UdpSocket {
+            streams: RefCell::new(Some(Streams { incoming, outgoing })),
+            socket,
+        })
+    }
+
+    /// Connects this socket to `addr`, after which [`send`](Self::send) and
+    /// [`recv`](Self::recv) can be used and datagrams from other addresses
+    /// are discarded.
+    ///
+    /// If `addr` resolves to multiple addresses then each one is tried in
+    /// turn until connecting succeeds.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        each_addr(addr, |addr| self.connect_addr(addr))
+    }
+
+    fn connect_addr(&self, addr: SocketAddr) -> io::Result<()> {
+        let mut streams = self.streams.borrow_mut();
+        // The previous streams must be dropped before new ones are created.
+        *streams = None;
+        let addr = remote_addr(addr)?;
+        let (incoming, outgoing) = self.socket.stream(Some(addr.into()))?;
+        *streams = Some(Streams { incoming, outgoing });
+        Ok(())
+    }
+
+    /// Sends `buf` as a single datagram to `addr`, returning the number of
+    /// bytes sent.
+    ///
+    /// If `addr` resolves to multiple addresses then the datagram is only sent
+    /// to the first one.
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no addresses to send data to")
+        })?;
+        self.send_many(&[(buf, Some(addr))])?;
+        Ok(buf.len())
+    }
+
+    /// Sends `buf` as a single datagram to the address this socket is
+    /// connected to, returning the number of bytes sent.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.send_many(&[(buf, None)])?;
+        Ok(buf.len())
+    }
+
+    /// Sends every datagram in `datagrams`, blocking until all of them have
+    /// been sent or queued.
+    ///
+    /// Datagrams without an address are sent to the address this socket is
+    /// connected to. The datagrams are passed to the host in batches as large
+    /// as it permits.
+    ///
+    /// Nothing is sent if any of the addresses is unspecified or has port 0.
+    pub fn send_many(&self, datagrams: &[(&[u8], Option<SocketAddr>)]) -> io::Result<()> {
+        // Check every address before sending anything so that an invalid
+        // one doesn't leave the datagrams partially sent.
+        for (_, addr) in datagrams {
+            if let Some(addr) = addr {
+                remote_addr(*addr)?;
+            }
+        }
+        let streams = self.streams()?;
+        let outgoing = &streams.outgoing;
+        let mut remaining = datagrams;
+        while !remaining.is_empty() {
+            let permit = outgoing.check_send()?;
+            if permit == 0 {
+                outgoing.subscribe().block();
+                continue;
+            }
+            let batch = batch(remaining, permit);
+            let n = batch.len();
+            let sent = outgoing.send(&batch)?;
+            remaining = &remaining[usize::try_from(sent).unwrap_or(n).min(n)..];
+        }
+        Ok(())
+    }
+
+    /// Receives a single datagram, blocking until one is available, and
+    /// returns the number of bytes read and the address it came from.
+    ///
+    /// If the datagram is larger than `buf` then the excess bytes are
+    /// discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (data, addr) = self.recv_many(1)?.pop().expect("at least one datagram");
+        Ok((truncate(&data, buf), addr))
+    }
+
+    /// Receives a single datagram from the address this socket is connected
+    /// to, returning the number of bytes read.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_from(buf).map(|(n, _)| n)
+    }
+
+    /// Receives up to `max` datagrams, blocking until at least one is
+    /// available, and returns their payloads along with the addresses they
+    /// came from.
+    pub fn recv_many(&self, max: usize) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+        let streams = self.streams()?;
+        let incoming = &streams.incoming;
+        let max = u64::try_from(max.max(1)).unwrap_or(u64::MAX);
+        loop {
+            let datagrams = incoming.receive(max)?;
+            if datagrams.is_empty() {
+                incoming.subscribe().block();
+                continue;
+            }
+            return Ok(datagrams
+                .into_iter()
+                .map(|d| (d.data, d.remote_address.into()))
+                .collect());
+        }
+    }
+
+    /// Returns the local address this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .local_address()
+            .map(SocketAddr::from)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the address this socket is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket
+            .remote_address()
+            .map(SocketAddr::from)
+            .map_err(io::Error::from)
+    }
+
+    /// Sets the hop limit of outgoing unicast packets, similar to `IP_TTL`
+    /// and `IPV6_UNICAST_HOPS`.
+    pub fn set_ttl(&self, ttl: u8) -> io::Result<()> {
+        self.socket
+            .set_unicast_hop_limit(nonzero(ttl)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the hop limit of outgoing unicast packets.
+    pub fn ttl(&self) -> io::Result<u8> {
+        self.socket.unicast_hop_limit().map_err(io::Error::from)
+    }
+
+    /// Sets the size of the kernel's receive buffer, similar to `SO_RCVBUF`.
+    pub fn set_receive_buffer_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_receive_buffer_size(nonzero(size)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the size of the kernel's receive buffer.
+    pub fn receive_buffer_size(&self) -> io::Result<u64> {
+        self.socket.receive_buffer_size().map_err(io::Error::from)
+    }
+
+    /// Sets the size of the kernel's send buffer, similar to `SO_SNDBUF`.
+    pub fn set_send_buffer_size(&self, size: u64) -> io::Result<()> {
+        self.socket
+            .set_send_buffer_size(nonzero(size)?)
+            .map_err(io::Error::from)
+    }
+
+    /// Returns the size of the kernel's send buffer.
+    pub fn send_buffer_size(&self) -> io::Result<u64> {
+        self.socket.send_buffer_size().map_err(io::Error::from)
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &udp::UdpSocket {
+        &self.socket
+    }
+
+    fn streams(&self) -> io::Result<Ref<'_, Streams>> {
+        Ref::filter_map(self.streams.borrow(), Option::as_ref).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotConnected,
+                "socket has no streams after a failed `connect`",
+            )
+        })
+    }
+}
+
+/// Converts as many of `datagrams` as the budget `permit` of
+/// [`OutgoingDatagramStream::check_send`] allows.
+fn batch(datagrams: &[(&[u8], Option<SocketAddr>)], permit: u64) -> Vec<OutgoingDatagram> {
+    let n = usize::try_from(permit)
+        .unwrap_or(usize::MAX)
+        .min(datagrams.len());
+    datagrams[..n]
+        .iter()
+        .map(|(data, addr)| OutgoingDatagram {
+            data: data.to_vec(),
+            remote_address: addr.map(Into::into),
+        })
+        .collect()
+}
+
+/// Copies the received `data` into `buf`, discarding the bytes that don't
+/// fit, and returns the number of bytes copied.
+fn truncate(data: &[u8], buf: &mut [u8]) -> usize {
+    let n = data.len().min(buf.len());
+    buf[..n].copy_from_slice(&data[..n]);
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let addr = "10.0.0.1:53".parse().unwrap();
+        let datagrams: [(&[u8], _); 3] = [(b"a", Some(addr)), (b"bc", None), (b"", Some(addr))];
+        assert_eq!(batch(&datagrams, 1).len(), 1);
+        assert_eq!(batch(&datagrams, 3).len(), 3);
+        assert_eq!(batch(&datagrams, u64::MAX).len(), 3);
+        assert!(batch(&[], 16).is_empty());
+
+        let batch = batch(&datagrams, 2);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch[0].data, b"a");
+        assert_eq!(batch[0].remote_address.map(SocketAddr::from), Some(addr));
+        assert_eq!(batch[1].data, b"bc");
+        assert!(batch[1].remote_address.is_none());
+    }
+
+    #[test]
+    fn truncated_datagrams() {
+        let mut buf = [0; 4];
+        assert_eq!(truncate(b"ab", &mut buf), 2);
+        assert_eq!(&buf, b"ab\0\0");
+        assert_eq!(truncate(b"vwxyz", &mut buf), 4);
+        assert_eq!(&buf, b"vwxy");
+        assert_eq!(truncate(b"abc", &mut []), 0);
+    }
+}
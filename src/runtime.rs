@@ -0,0 +1,550 @@
+//! A small single-threaded async executor driven by [`wasi:io/poll`].
+//!
+//! [`wasi:io/poll`]: https://github.com/WebAssembly/wasi-io/blob/main/wit/poll.wit
+//!
+//! Components have exactly one thread of execution and block on I/O through
+//! [`Pollable`] resources. This module turns those pollables into Rust
+//! futures so `async` code can be used inside of exported functions such as
+//! [`exports::cli::run::Guest::run`](crate::exports::cli::run::Guest::run) or
+//! [`exports::http::incoming_handler::Guest::handle`](crate::exports::http::incoming_handler::Guest::handle).
+//!
+//! The executor is made of two pieces:
+//!
+//! * [`block_on`] drives a future, along with any tasks created with
+//!   [`spawn`], to completion on the current thread.
+//! * A reactor which owns the [`Pollable`] of every pending [`WaitFor`]
+//!   future. Whenever no task can make progress the reactor makes a single
+//!   call to [`poll::poll`] with all registered pollables and wakes exactly
+//!   the tasks whose pollables became ready.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! struct MyCliRunner;
+//!
+//! impl wasi::exports::cli::run::Guest for MyCliRunner {
+//!     fn run() -> Result<(), ()> {
+//!         wasi::runtime::block_on(async {
+//!             let a = wasi::runtime::spawn(async {
+//!                 wasi::runtime::sleep(Duration::from_millis(10)).await;
+//!                 1
+//!             });
+//!             let b = wasi::runtime::spawn(async {
+//!                 wasi::runtime::sleep(Duration::from_millis(20)).await;
+//!                 2
+//!             });
+//!             assert_eq!(a.await + b.await, 3);
+//!         });
+//!         Ok(())
+//!     }
+//! }
+//!
+//! wasi::cli::command::export!(MyCliRunner);
+//! ```
+
+use crate::clocks::monotonic_clock;
+use crate::io::poll::{self, Pollable};
+use std::boxed::Box;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread_local;
+use std::time::Duration;
+use std::vec::Vec;
+
+thread_local! {
+    static EXECUTOR: Executor = Executor::default();
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+#[derive(Default)]
+struct Executor {
+    /// Slab of spawned tasks. A slot is `None` while its task is being polled
+    /// or once the task has completed.
+    tasks: RefCell<Vec<Option<Task>>>,
+    /// Indices in `tasks` which are free to be reused.
+    free: RefCell<Vec<usize>>,
+    queue: Arc<ReadyQueue>,
+    reactor: RefCell<Reactor>,
+    running: Cell<bool>,
+}
+
+/// Tasks which have been woken and need to be polled on the next turn.
+#[derive(Default)]
+struct ReadyQueue {
+    tasks: Mutex<VecDeque<usize>>,
+    main: AtomicBool,
+}
+
+/// Waker for either a spawned task (`Some(index)`) or the future passed to
+/// `block_on` (`None`).
+struct TaskWaker {
+    task: Option<usize>,
+    queue: Arc<ReadyQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        match self.task {
+            Some(index) => self.queue.tasks.lock().unwrap().push_back(index),
+            None => self.queue.main.store(true, Ordering::SeqCst),
+        }
+    }
+}
+
+impl Executor {
+    fn waker(&self, task: Option<usize>) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            task,
+            queue: self.queue.clone(),
+        }))
+    }
+
+    fn insert(&self, task: Task) {
+        let mut tasks = self.tasks.borrow_mut();
+        let index = match self.free.borrow_mut().pop() {
+            Some(index) => {
+                tasks[index] = Some(task);
+                index
+            }
+            None => {
+                tasks.push(Some(task));
+                tasks.len() - 1
+            }
+        };
+        self.queue.tasks.lock().unwrap().push_back(index);
+    }
+
+    /// Polls every spawned task which has been woken, including tasks woken
+    /// while this function is running.
+    fn run_ready_tasks(&self) {
+        loop {
+            let Some(index) = self.queue.tasks.lock().unwrap().pop_front() else {
+                break;
+            };
+            // Take the task out of its slot so it can spawn more tasks while
+            // being polled. A missing task has already completed and this is
+            // a stale wakeup.
+            let Some(mut task) = self.tasks.borrow_mut()[index].take() else {
+                continue;
+            };
+            let waker = self.waker(Some(index));
+            match task.as_mut().poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(()) => self.free.borrow_mut().push(index),
+                Poll::Pending => self.tasks.borrow_mut()[index] = Some(task),
+            }
+        }
+    }
+
+    fn has_ready_work(&self) -> bool {
+        self.queue.main.load(Ordering::SeqCst) || !self.queue.tasks.lock().unwrap().is_empty()
+    }
+}
+
+/// Runs `future` to completion on the current thread, returning its output.
+///
+/// Tasks created with [`spawn`] are run alongside `future`. Any spawned tasks
+/// which haven't completed by the time `future` finishes are kept and resume
+/// running on the next call to `block_on`.
+///
+/// # Panics
+///
+/// Panics if called from within another call to `block_on`, or if `future`
+/// can never complete because no task is ready and no [`WaitFor`] is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    EXECUTOR.with(|exec| {
+        assert!(
+            !exec.running.replace(true),
+            "`block_on` cannot be called recursively"
+        );
+        let _running = ResetOnDrop(&exec.running);
+
+        let mut future = pin!(future);
+        let waker = exec.waker(None);
+        let mut cx = Context::from_waker(&waker);
+        exec.queue.main.store(true, Ordering::SeqCst);
+        loop {
+            if exec.queue.main.swap(false, Ordering::SeqCst) {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+            exec.run_ready_tasks();
+            if exec.has_ready_work() {
+                continue;
+            }
+            if !exec.reactor.borrow_mut().block_until_ready() {
+                panic!(
+                    "`block_on` future cannot make progress: no tasks are waiting on a pollable"
+                );
+            }
+        }
+    })
+}
+
+struct ResetOnDrop<'a>(&'a Cell<bool>);
+
+impl Drop for ResetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+/// Spawns a new task onto the current thread's executor.
+///
+/// The task starts running during the current, or next, call to [`block_on`].
+/// The returned [`JoinHandle`] is a future resolving to the task's output;
+/// dropping it detaches the task, which keeps running in the background.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    let state = Rc::new(RefCell::new(JoinState {
+        output: None,
+        waker: None,
+    }));
+    let task_state = state.clone();
+    EXECUTOR.with(|exec| {
+        exec.insert(Box::pin(async move {
+            let output = future.await;
+            let waker = {
+                let mut state = task_state.borrow_mut();
+                state.output = Some(output);
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }))
+    });
+    JoinHandle { state }
+}
+
+/// A handle to a task created with [`spawn`], resolving to its output.
+pub struct JoinHandle<T> {
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns whether the task has completed.
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().output.is_some()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Returns a future which resolves once `pollable` is ready.
+///
+/// The pollable is owned by the reactor until the returned future is dropped,
+/// so the future must be dropped before the resource the pollable was created
+/// from, as is required of the pollable itself.
+///
+/// ```no_run
+/// # async fn example() {
+/// let stdin = wasi::cli::stdin::get_stdin();
+/// wasi::runtime::wait_for(stdin.subscribe()).await;
+/// let bytes = stdin.read(1024).unwrap();
+/// # }
+/// ```
+pub fn wait_for(pollable: Pollable) -> WaitFor {
+    let key = EXECUTOR.with(|exec| exec.reactor.borrow_mut().register(pollable));
+    WaitFor { key }
+}
+
+/// Future returned by [`wait_for`].
+pub struct WaitFor {
+    key: usize,
+}
+
+impl WaitFor {
+    /// Returns whether the pollable has become ready, without blocking.
+    pub fn is_ready(&self) -> bool {
+        EXECUTOR.with(|exec| {
+            let reactor = exec.reactor.borrow();
+            let registration = reactor.get(self.key);
+            registration.ready || registration.pollable.ready()
+        })
+    }
+}
+
+impl Future for WaitFor {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        EXECUTOR.with(|exec| {
+            let mut reactor = exec.reactor.borrow_mut();
+            let registration = reactor.get_mut(self.key);
+            if registration.ready || registration.pollable.ready() {
+                registration.ready = true;
+                return Poll::Ready(());
+            }
+            match &mut registration.waker {
+                Some(waker) => waker.clone_from(cx.waker()),
+                None => registration.waker = Some(cx.waker().clone()),
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl Drop for WaitFor {
+    fn drop(&mut self) {
+        // The thread-local may already be gone if this is dropped during
+        // thread teardown, in which case the pollable went with it.
+        let _ = EXECUTOR.try_with(|exec| exec.reactor.borrow_mut().deregister(self.key));
+    }
+}
+
+/// Returns a future which resolves once `duration` has elapsed, as measured
+/// by [`monotonic_clock`].
+pub fn sleep(duration: Duration) -> WaitFor {
+    let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    wait_for(monotonic_clock::subscribe_duration(nanos))
+}
+
+#[derive(Default)]
+struct Reactor {
+    registrations: Vec<Option<Registration>>,
+    free: Vec<usize>,
+}
+
+struct Registration {
+    pollable: Pollable,
+    /// The waker of the task waiting on this pollable, set once the
+    /// `WaitFor` has been polled and found to not be ready.
+    waker: Option<Waker>,
+    ready: bool,
+}
+
+impl Reactor {
+    fn register(&mut self, pollable: Pollable) -> usize {
+        let registration = Registration {
+            pollable,
+            waker: None,
+            ready: false,
+        };
+        match self.free.pop() {
+            Some(key) => {
+                self.registrations[key] = Some(registration);
+                key
+            }
+            None => {
+                self.registrations.push(Some(registration));
+                self.registrations.len() - 1
+            }
+        }
+    }
+
+    fn deregister(&mut self, key: usize) {
+        self.registrations[key] = None;
+        self.free.push(key);
+    }
+
+    fn get(&self, key: usize) -> &Registration {
+        self.registrations[key].as_ref().unwrap()
+    }
+
+    fn get_mut(&mut self, key: usize) -> &mut Registration {
+        self.registrations[key].as_mut().unwrap()
+    }
+
+    /// Blocks in a single call to [`poll::poll`] until at least one of the
+    /// pollables that a task is waiting on is ready, then wakes the tasks
+    /// waiting on each ready pollable.
+    ///
+    /// Returns `false` without blocking if no task is waiting on a pollable.
+    fn block_until_ready(&mut self) -> bool {
+        let mut keys = Vec::new();
+        let mut pollables = Vec::new();
+        for (key, registration) in self.registrations.iter().enumerate() {
+            if let Some(
+                registration @ Registration {
+                    waker: Some(_),
+                    ready: false,
+                    ..
+                },
+            ) = registration
+            {
+                keys.push(key);
+                pollables.push(&registration.pollable);
+            }
+        }
+        if pollables.is_empty() {
+            return false;
+        }
+
+        let ready = poll::poll(&pollables);
+        drop(pollables);
+        for index in ready {
+            let registration = self.get_mut(keys[index as usize]);
+            registration.ready = true;
+            if let Some(waker) = registration.waker.take() {
+                waker.wake();
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    /// A future which is pending `n` times, waking itself each time.
+    struct Yield(usize);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn ready_futures() {
+        assert_eq!(block_on(async { 1 }), 1);
+        assert_eq!(block_on(std::future::ready("done")), "done");
+        assert_eq!(
+            block_on(async {
+                Yield(3).await;
+                2
+            }),
+            2
+        );
+    }
+
+    #[test]
+    fn join_handles() {
+        let output = block_on(async {
+            let a = spawn(async {
+                Yield(5).await;
+                1
+            });
+            let b = spawn(async { 2 });
+            assert!(!a.is_finished() && !b.is_finished());
+            // `a` is woken by its task finishing after `b` has.
+            a.await + b.await
+        });
+        assert_eq!(output, 3);
+    }
+
+    #[test]
+    fn finished_join_handles() {
+        let handle = spawn(async { 4 });
+        block_on(Yield(1));
+        assert!(handle.is_finished());
+        assert_eq!(block_on(handle), 4);
+    }
+
+    #[test]
+    fn detached_tasks() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let task_log = log.clone();
+        block_on(async move {
+            let handle = spawn(async move {
+                Yield(3).await;
+                task_log.borrow_mut().push("task");
+            });
+            drop(handle);
+        });
+        // The task wasn't finished when `block_on` returned and resumes on
+        // the next call.
+        assert!(log.borrow().is_empty());
+        block_on(Yield(10));
+        assert_eq!(*log.borrow(), ["task"]);
+    }
+
+    #[test]
+    fn nested_spawns() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let outer_log = log.clone();
+        let output = block_on(async move {
+            spawn(async move {
+                outer_log.borrow_mut().push("outer");
+                let inner_log = outer_log.clone();
+                let inner = spawn(async move {
+                    Yield(2).await;
+                    inner_log.borrow_mut().push("inner");
+                    5
+                });
+                inner.await * 2
+            })
+            .await
+        });
+        assert_eq!(output, 10);
+        assert_eq!(*log.borrow(), ["outer", "inner"]);
+    }
+
+    #[test]
+    fn reused_slots() {
+        let outputs = block_on(async {
+            let mut outputs = vec![];
+            for i in 0..4 {
+                let handles = (0..3)
+                    .map(|j| spawn(async move { i * 3 + j }))
+                    .collect::<Vec<_>>();
+                for handle in handles {
+                    outputs.push(handle.await);
+                }
+            }
+            outputs
+        });
+        assert_eq!(outputs, (0..12).collect::<Vec<_>>());
+        EXECUTOR.with(|exec| assert_eq!(exec.tasks.borrow().len(), 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot make progress")]
+    fn stuck_futures() {
+        block_on(std::future::pending::<()>());
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be called recursively")]
+    fn recursive_block_on() {
+        block_on(async { block_on(async {}) });
+    }
+
+    #[test]
+    fn block_on_after_panic() {
+        let result = std::panic::catch_unwind(|| block_on(async { panic!("oops") }));
+        assert!(result.is_err());
+        assert_eq!(block_on(async { 1 }), 1);
+    }
+}
@@ -0,0 +1,315 @@
+//! Conversions between the address types of
+//! [`wasi:sockets/network`](crate::sockets::network) and [`core::net`].
+//!
+//! Every conversion is lossless, including the flow info and scope id of IPv6
+//! socket addresses. The bare `Ipv4Address` and `Ipv6Address` tuples can be
+//! converted by wrapping them in an `IpAddress` first.
+
+use crate::sockets::network::{
+    IpAddress, IpAddressFamily, IpSocketAddress, Ipv4SocketAddress, Ipv6SocketAddress,
+};
+use core::fmt;
+use core::net::{
+    AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
+};
+use core::str::FromStr;
+
+impl From<Ipv4Addr> for IpAddress {
+    fn from(addr: Ipv4Addr) -> IpAddress {
+        let [a, b, c, d] = addr.octets();
+        IpAddress::Ipv4((a, b, c, d))
+    }
+}
+
+impl From<Ipv6Addr> for IpAddress {
+    fn from(addr: Ipv6Addr) -> IpAddress {
+        let [a, b, c, d, e, f, g, h] = addr.segments();
+        IpAddress::Ipv6((a, b, c, d, e, f, g, h))
+    }
+}
+
+impl From<IpAddr> for IpAddress {
+    fn from(addr: IpAddr) -> IpAddress {
+        match addr {
+            IpAddr::V4(addr) => addr.into(),
+            IpAddr::V6(addr) => addr.into(),
+        }
+    }
+}
+
+impl From<IpAddress> for IpAddr {
+    fn from(addr: IpAddress) -> IpAddr {
+        match addr {
+            IpAddress::Ipv4((a, b, c, d)) => IpAddr::V4(Ipv4Addr::new(a, b, c, d)),
+            IpAddress::Ipv6((a, b, c, d, e, f, g, h)) => {
+                IpAddr::V6(Ipv6Addr::new(a, b, c, d, e, f, g, h))
+            }
+        }
+    }
+}
+
+impl From<SocketAddrV4> for Ipv4SocketAddress {
+    fn from(addr: SocketAddrV4) -> Ipv4SocketAddress {
+        let [a, b, c, d] = addr.ip().octets();
+        Ipv4SocketAddress {
+            port: addr.port(),
+            address: (a, b, c, d),
+        }
+    }
+}
+
+impl From<Ipv4SocketAddress> for SocketAddrV4 {
+    fn from(addr: Ipv4SocketAddress) -> SocketAddrV4 {
+        let (a, b, c, d) = addr.address;
+        SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), addr.port)
+    }
+}
+
+impl From<SocketAddrV6> for Ipv6SocketAddress {
+    fn from(addr: SocketAddrV6) -> Ipv6SocketAddress {
+        let [a, b, c, d, e, f, g, h] = addr.ip().segments();
+        Ipv6SocketAddress {
+            port: addr.port(),
+            flow_info: addr.flowinfo(),
+            address: (a, b, c, d, e, f, g, h),
+            scope_id: addr.scope_id(),
+        }
+    }
+}
+
+impl From<Ipv6SocketAddress> for SocketAddrV6 {
+    fn from(addr: Ipv6SocketAddress) -> SocketAddrV6 {
+        let (a, b, c, d, e, f, g, h) = addr.address;
+        SocketAddrV6::new(
+            Ipv6Addr::new(a, b, c, d, e, f, g, h),
+            addr.port,
+            addr.flow_info,
+            addr.scope_id,
+        )
+    }
+}
+
+impl From<SocketAddrV4> for IpSocketAddress {
+    fn from(addr: SocketAddrV4) -> IpSocketAddress {
+        IpSocketAddress::Ipv4(addr.into())
+    }
+}
+
+impl From<SocketAddrV6> for IpSocketAddress {
+    fn from(addr: SocketAddrV6) -> IpSocketAddress {
+        IpSocketAddress::Ipv6(addr.into())
+    }
+}
+
+impl From<SocketAddr> for IpSocketAddress {
+    fn from(addr: SocketAddr) -> IpSocketAddress {
+        match addr {
+            SocketAddr::V4(addr) => addr.into(),
+            SocketAddr::V6(addr) => addr.into(),
+        }
+    }
+}
+
+impl From<IpSocketAddress> for SocketAddr {
+    fn from(addr: IpSocketAddress) -> SocketAddr {
+        match addr {
+            IpSocketAddress::Ipv4(addr) => SocketAddr::V4(addr.into()),
+            IpSocketAddress::Ipv6(addr) => SocketAddr::V6(addr.into()),
+        }
+    }
+}
+
+impl IpAddress {
+    /// Returns the family of this address.
+    pub fn family(&self) -> IpAddressFamily {
+        match self {
+            IpAddress::Ipv4(_) => IpAddressFamily::Ipv4,
+            IpAddress::Ipv6(_) => IpAddressFamily::Ipv6,
+        }
+    }
+}
+
+impl IpSocketAddress {
+    /// Returns the family of this address.
+    pub fn family(&self) -> IpAddressFamily {
+        match self {
+            IpSocketAddress::Ipv4(_) => IpAddressFamily::Ipv4,
+            IpSocketAddress::Ipv6(_) => IpAddressFamily::Ipv6,
+        }
+    }
+
+    /// Returns the IP address of this socket address.
+    pub fn ip(&self) -> IpAddress {
+        match self {
+            IpSocketAddress::Ipv4(addr) => IpAddress::Ipv4(addr.address),
+            IpSocketAddress::Ipv6(addr) => IpAddress::Ipv6(addr.address),
+        }
+    }
+
+    /// Returns the port of this socket address.
+    pub fn port(&self) -> u16 {
+        match self {
+            IpSocketAddress::Ipv4(addr) => addr.port,
+            IpSocketAddress::Ipv6(addr) => addr.port,
+        }
+    }
+}
+
+impl From<IpAddress> for IpAddressFamily {
+    fn from(addr: IpAddress) -> IpAddressFamily {
+        addr.family()
+    }
+}
+
+impl From<IpSocketAddress> for IpAddressFamily {
+    fn from(addr: IpSocketAddress) -> IpAddressFamily {
+        addr.family()
+    }
+}
+
+impl From<IpAddr> for IpAddressFamily {
+    fn from(addr: IpAddr) -> IpAddressFamily {
+        match addr {
+            IpAddr::V4(_) => IpAddressFamily::Ipv4,
+            IpAddr::V6(_) => IpAddressFamily::Ipv6,
+        }
+    }
+}
+
+impl From<SocketAddr> for IpAddressFamily {
+    fn from(addr: SocketAddr) -> IpAddressFamily {
+        addr.ip().into()
+    }
+}
+
+impl fmt::Display for IpAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        IpAddr::from(*self).fmt(f)
+    }
+}
+
+impl fmt::Display for IpSocketAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        SocketAddr::from(*self).fmt(f)
+    }
+}
+
+impl FromStr for IpAddress {
+    type Err = AddrParseError;
+
+    /// Parses an address in the same format as [`IpAddr`].
+    fn from_str(s: &str) -> Result<IpAddress, AddrParseError> {
+        s.parse::<IpAddr>().map(IpAddress::from)
+    }
+}
+
+impl FromStr for IpSocketAddress {
+    type Err = AddrParseError;
+
+    /// Parses an address in the same format as [`SocketAddr`].
+    fn from_str(s: &str) -> Result<IpSocketAddress, AddrParseError> {
+        s.parse::<SocketAddr>().map(IpSocketAddress::from)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    const V4: &str = "192.0.2.1:8080";
+    const V6: &str = "[2001:db8::1]:443";
+
+    #[test]
+    fn ip_addr_round_trip() {
+        for s in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "::",
+            "::1",
+            "2001:db8::ff00:42:8329",
+        ] {
+            let ip = s.parse::<IpAddr>().unwrap();
+            let addr = IpAddress::from(ip);
+            assert_eq!(IpAddr::from(addr), ip);
+            assert_eq!(addr.to_string(), s);
+            assert_eq!(IpAddr::from(s.parse::<IpAddress>().unwrap()), ip);
+        }
+    }
+
+    #[test]
+    fn ip_addr_components() {
+        let IpAddress::Ipv4(octets) = IpAddress::from(Ipv4Addr::new(10, 1, 2, 3)) else {
+            panic!("expected an IPv4 address");
+        };
+        assert_eq!(octets, (10, 1, 2, 3));
+
+        let IpAddress::Ipv6(segments) = "2001:db8::1".parse::<IpAddress>().unwrap() else {
+            panic!("expected an IPv6 address");
+        };
+        assert_eq!(segments, (0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+    }
+
+    #[test]
+    fn socket_addr_round_trip() {
+        for s in [V4, V6, "0.0.0.0:0", "[::]:65535"] {
+            let socket = s.parse::<SocketAddr>().unwrap();
+            let addr = IpSocketAddress::from(socket);
+            assert_eq!(SocketAddr::from(addr), socket);
+            assert_eq!(addr.to_string(), s);
+            assert_eq!(addr.port(), socket.port());
+            assert_eq!(IpAddr::from(addr.ip()), socket.ip());
+        }
+    }
+
+    #[test]
+    fn ipv6_flow_info_and_scope_id() {
+        let socket = SocketAddrV6::new("fe80::1".parse().unwrap(), 80, 0x12345, 7);
+        let addr = Ipv6SocketAddress::from(socket);
+        assert_eq!(addr.port, 80);
+        assert_eq!(addr.flow_info, 0x12345);
+        assert_eq!(addr.scope_id, 7);
+        assert_eq!(addr.address, (0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(SocketAddrV6::from(addr), socket);
+
+        let addr = IpSocketAddress::from(SocketAddr::V6(socket));
+        assert_eq!(SocketAddr::from(addr), SocketAddr::V6(socket));
+        assert_eq!(addr.to_string(), "[fe80::1%7]:80");
+        let parsed = "[fe80::1%7]:80".parse::<IpSocketAddress>().unwrap();
+        let IpSocketAddress::Ipv6(parsed) = parsed else {
+            panic!("expected an IPv6 address");
+        };
+        assert_eq!(parsed.scope_id, 7);
+    }
+
+    #[test]
+    fn families() {
+        let v4 = V4.parse::<IpSocketAddress>().unwrap();
+        let v6 = V6.parse::<IpSocketAddress>().unwrap();
+        assert_eq!(v4.family(), IpAddressFamily::Ipv4);
+        assert_eq!(v6.family(), IpAddressFamily::Ipv6);
+        assert_eq!(v4.ip().family(), IpAddressFamily::Ipv4);
+        assert_eq!(IpAddressFamily::from(v6.ip()), IpAddressFamily::Ipv6);
+        assert_eq!(
+            IpAddressFamily::from(V4.parse::<SocketAddr>().unwrap()),
+            IpAddressFamily::Ipv4
+        );
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        for s in ["", "localhost", "1.2.3", "1.2.3.4.5", "::g", "[::1]"] {
+            assert!(s.parse::<IpAddress>().is_err(), "{s}");
+        }
+        for s in [
+            "",
+            "1.2.3.4",
+            "1.2.3.4:",
+            "1.2.3.4:65536",
+            "::1:80",
+            "[::1]",
+        ] {
+            assert!(s.parse::<IpSocketAddress>().is_err(), "{s}");
+        }
+    }
+}
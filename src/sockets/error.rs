@@ -0,0 +1,161 @@
+//! Conversions from [`ErrorCode`] to [`std::io::Error`].
+
+use crate::sockets::network::ErrorCode;
+#[cfg(feature = "std")]
+use std::io;
+
+impl ErrorCode {
+    /// Returns the `errno` value of this error as defined by `wasi-libc`, the
+    /// same value returned by [`std::io::Error::raw_os_error`] for errors
+    /// raised by the standard library on WASI targets.
+    ///
+    /// Returns `None` for errors without a POSIX equivalent, such as name
+    /// resolution failures which are reported through `EAI_*` codes instead.
+    pub fn raw_os_error(&self) -> Option<i32> {
+        Some(match self {
+            ErrorCode::AccessDenied => 2,
+            ErrorCode::NotSupported => 58,
+            ErrorCode::InvalidArgument => 28,
+            ErrorCode::OutOfMemory => 48,
+            ErrorCode::Timeout => 73,
+            ErrorCode::ConcurrencyConflict => 7,
+            ErrorCode::WouldBlock => 6,
+            ErrorCode::NewSocketLimit => 33,
+            ErrorCode::AddressNotBindable => 4,
+            ErrorCode::AddressInUse => 3,
+            ErrorCode::RemoteUnreachable => 23,
+            ErrorCode::ConnectionRefused => 14,
+            ErrorCode::ConnectionReset => 15,
+            ErrorCode::ConnectionAborted => 13,
+            ErrorCode::DatagramTooLarge => 35,
+            ErrorCode::Unknown
+            | ErrorCode::NotInProgress
+            | ErrorCode::InvalidState
+            | ErrorCode::NameUnresolvable
+            | ErrorCode::TemporaryResolverFailure
+            | ErrorCode::PermanentResolverFailure => return None,
+        })
+    }
+
+    /// Returns the [`io::ErrorKind`] corresponding to this error.
+    #[cfg(feature = "std")]
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            ErrorCode::AccessDenied => io::ErrorKind::PermissionDenied,
+            ErrorCode::NotSupported => io::ErrorKind::Unsupported,
+            ErrorCode::InvalidArgument => io::ErrorKind::InvalidInput,
+            ErrorCode::OutOfMemory => io::ErrorKind::OutOfMemory,
+            ErrorCode::Timeout => io::ErrorKind::TimedOut,
+            ErrorCode::WouldBlock => io::ErrorKind::WouldBlock,
+            ErrorCode::AddressNotBindable => io::ErrorKind::AddrNotAvailable,
+            ErrorCode::AddressInUse => io::ErrorKind::AddrInUse,
+            ErrorCode::RemoteUnreachable => io::ErrorKind::HostUnreachable,
+            ErrorCode::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            ErrorCode::ConnectionReset => io::ErrorKind::ConnectionReset,
+            ErrorCode::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            ErrorCode::NameUnresolvable => io::ErrorKind::NotFound,
+            ErrorCode::Unknown
+            | ErrorCode::ConcurrencyConflict
+            | ErrorCode::NotInProgress
+            | ErrorCode::InvalidState
+            | ErrorCode::NewSocketLimit
+            | ErrorCode::DatagramTooLarge
+            | ErrorCode::TemporaryResolverFailure
+            | ErrorCode::PermanentResolverFailure => io::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ErrorCode> for io::Error {
+    fn from(code: ErrorCode) -> io::Error {
+        io::Error::new(code.kind(), code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every error code along with its `errno` name and value in `wasi-libc`,
+    /// if it has one.
+    const ERRNO: &[(ErrorCode, &str, Option<i32>)] = &[
+        (ErrorCode::Unknown, "", None),
+        (ErrorCode::AccessDenied, "EACCES", Some(2)),
+        (ErrorCode::NotSupported, "ENOTSUP", Some(58)),
+        (ErrorCode::InvalidArgument, "EINVAL", Some(28)),
+        (ErrorCode::OutOfMemory, "ENOMEM", Some(48)),
+        (ErrorCode::Timeout, "ETIMEDOUT", Some(73)),
+        (ErrorCode::ConcurrencyConflict, "EALREADY", Some(7)),
+        (ErrorCode::NotInProgress, "", None),
+        (ErrorCode::WouldBlock, "EAGAIN", Some(6)),
+        (ErrorCode::InvalidState, "", None),
+        (ErrorCode::NewSocketLimit, "EMFILE", Some(33)),
+        (ErrorCode::AddressNotBindable, "EADDRNOTAVAIL", Some(4)),
+        (ErrorCode::AddressInUse, "EADDRINUSE", Some(3)),
+        (ErrorCode::RemoteUnreachable, "EHOSTUNREACH", Some(23)),
+        (ErrorCode::ConnectionRefused, "ECONNREFUSED", Some(14)),
+        (ErrorCode::ConnectionReset, "ECONNRESET", Some(15)),
+        (ErrorCode::ConnectionAborted, "ECONNABORTED", Some(13)),
+        (ErrorCode::DatagramTooLarge, "EMSGSIZE", Some(35)),
+        (ErrorCode::NameUnresolvable, "", None),
+        (ErrorCode::TemporaryResolverFailure, "", None),
+        (ErrorCode::PermanentResolverFailure, "", None),
+    ];
+
+    #[test]
+    fn raw_os_errors() {
+        for (code, name, errno) in ERRNO {
+            assert_eq!(code.raw_os_error(), *errno, "{code:?} ({name})");
+        }
+    }
+
+    #[test]
+    fn raw_os_errors_are_distinct() {
+        for (i, (_, a, x)) in ERRNO.iter().enumerate() {
+            for (_, b, y) in &ERRNO[i + 1..] {
+                if x.is_some() {
+                    assert_ne!(x, y, "{a} and {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn kinds() {
+        use io::ErrorKind::*;
+        let kinds = [
+            (ErrorCode::AccessDenied, PermissionDenied),
+            (ErrorCode::NotSupported, Unsupported),
+            (ErrorCode::InvalidArgument, InvalidInput),
+            (ErrorCode::OutOfMemory, OutOfMemory),
+            (ErrorCode::Timeout, TimedOut),
+            (ErrorCode::WouldBlock, WouldBlock),
+            (ErrorCode::AddressNotBindable, AddrNotAvailable),
+            (ErrorCode::AddressInUse, AddrInUse),
+            (ErrorCode::RemoteUnreachable, HostUnreachable),
+            (ErrorCode::ConnectionRefused, ConnectionRefused),
+            (ErrorCode::ConnectionReset, ConnectionReset),
+            (ErrorCode::ConnectionAborted, ConnectionAborted),
+            (ErrorCode::NameUnresolvable, NotFound),
+            (ErrorCode::Unknown, Other),
+            (ErrorCode::InvalidState, Other),
+            (ErrorCode::TemporaryResolverFailure, Other),
+        ];
+        for (code, kind) in kinds {
+            assert_eq!(code.kind(), kind, "{code:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn into_io_error() {
+        for (code, _, _) in ERRNO {
+            let err = io::Error::from(*code);
+            assert_eq!(err.kind(), code.kind(), "{code:?}");
+            let inner = err.get_ref().and_then(|e| e.downcast_ref::<ErrorCode>());
+            assert_eq!(inner, Some(code), "{code:?}");
+        }
+    }
+}